@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use crate::input::{Message, Mode};
+
+// a random IV lands this close to the previous one with negligible probability
+const PREDICTABLE_IV_DISTANCE: u128 = 1 << 32;
+
+/**
+ * Interprets a 16 byte block as a big-endian 128-bit integer
+ */
+fn block_to_u128(block: &[u8]) -> u128 {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&block[0..16]);
+    u128::from_be_bytes(bytes)
+}
+
+/**
+ * Checks IVs of CBC messages (IV-prefixed ciphertexts), ECB messages are reported as violations
+ * Returns a human readable finding for every reused or predictable IV
+ */
+pub fn audit_ivs(messages: &[Message]) -> Vec<String> {
+    let mut findings: Vec<String> = Vec::new();
+
+    // first message which used given (key, IV) pair
+    let mut first_use: HashMap<(Vec<u8>, u128), usize> = HashMap::new();
+
+    // last message encrypted under given key
+    let mut last_message: HashMap<Vec<u8>, (usize, &Vec<u8>)> = HashMap::new();
+
+    for (index, message) in messages.iter().enumerate() {
+
+        // ECB has no IV to audit, the mode itself is the violation
+        if message.mode == Mode::Ecb {
+            findings.push(format!("message {}: ECB mode leaks equal plaintext blocks", index));
+            continue;
+        }
+
+        let key = &message.key;
+        let ciphertext = &message.ciphertext;
+        let iv = block_to_u128(&ciphertext[0..16]);
+
+        if iv == 0 {
+            findings.push(format!("message {}: IV is all zeros", index));
+        }
+        if ciphertext[0..16] == key[..] {
+            findings.push(format!("message {}: IV is equal to the key", index));
+        }
+
+        // the same IV under the same key leaks equality of plaintext prefixes
+        if let Some(first) = first_use.get(&(key.clone(), iv)) {
+            findings.push(format!("message {}: reuses the IV of message {}", index, first));
+        } else {
+            first_use.insert((key.clone(), iv), index);
+        }
+
+        // an IV derived from the previous message is known to the attacker in advance
+        if let Some((previous_index, previous_ciphertext)) = last_message.get(key) {
+            let previous_iv = block_to_u128(&previous_ciphertext[0..16]);
+            let distance = iv.wrapping_sub(previous_iv);
+            let last_block = &previous_ciphertext[previous_ciphertext.len() - 16..];
+
+            if distance != 0 && distance < PREDICTABLE_IV_DISTANCE {
+                findings.push(format!("message {}: IV is sequential (IV of message {} + {})",
+                    index, previous_index, distance));
+            }
+            if ciphertext[0..16] == *last_block {
+                findings.push(format!("message {}: IV is the last ciphertext block of message {}",
+                    index, previous_index));
+            }
+        }
+        last_message.insert(key.clone(), (index, ciphertext));
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::random_bytes;
+    use super::*;
+
+    fn message(key: &[u8], iv: &[u8], n_blocks: usize) -> Message {
+        let mut ciphertext = iv.to_vec();
+        ciphertext.extend(random_bytes(16 * n_blocks));
+        Message { key: key.to_vec(), ciphertext, expected_plaintext: None, mode: Mode::Cbc }
+    }
+
+    fn add_to_iv(iv: &[u8], n: u128) -> Vec<u8> {
+        block_to_u128(iv).wrapping_add(n).to_be_bytes().to_vec()
+    }
+
+    #[test]
+    fn clean() {
+        let key = random_bytes(16);
+        let messages: Vec<Message> = (0..20).map(|_| message(&key, &random_bytes(16), 3)).collect();
+        assert!(audit_ivs(&messages).is_empty());
+
+        // the same IV under different keys
+        let iv = random_bytes(16);
+        assert!(audit_ivs(&[message(&random_bytes(16), &iv, 2), message(&random_bytes(16), &iv, 2)]).is_empty());
+    }
+
+    #[test]
+    fn reused_iv() {
+        let key = random_bytes(16);
+        let iv = random_bytes(16);
+        let messages = [message(&key, &iv, 2), message(&key, &random_bytes(16), 2), message(&key, &iv, 1)];
+        assert_eq!(audit_ivs(&messages), ["message 2: reuses the IV of message 0"]);
+    }
+
+    #[test]
+    fn sequential_ivs() {
+        let key = random_bytes(16);
+        let iv = random_bytes(16);
+        let messages = [message(&key, &iv, 2), message(&key, &add_to_iv(&iv, 1), 2), message(&key, &add_to_iv(&iv, 1 << 40), 2)];
+        assert_eq!(audit_ivs(&messages), ["message 1: IV is sequential (IV of message 0 + 1)"]);
+    }
+
+    #[test]
+    fn iv_equal_to_key() {
+        let key = random_bytes(16);
+        assert_eq!(audit_ivs(&[message(&key, &key, 2)]), ["message 0: IV is equal to the key"]);
+        assert_eq!(audit_ivs(&[message(&key, &[0u8; 16], 2)]), ["message 0: IV is all zeros"]);
+    }
+
+    #[test]
+    fn chained_iv() {
+        let key = random_bytes(16);
+        let first = message(&key, &random_bytes(16), 3);
+        let last_block = first.ciphertext[first.ciphertext.len() - 16..].to_vec();
+        let messages = [first, message(&key, &last_block, 2)];
+        assert_eq!(audit_ivs(&messages), ["message 1: IV is the last ciphertext block of message 0"]);
+    }
+
+    #[test]
+    fn ecb() {
+        let key = random_bytes(16);
+        let messages = [Message { key: key.clone(), ciphertext: random_bytes(32), expected_plaintext: None, mode: Mode::Ecb }];
+        assert_eq!(audit_ivs(&messages), ["message 0: ECB mode leaks equal plaintext blocks"]);
+    }
+}
@@ -0,0 +1,88 @@
+/**
+ * Ciphertext modified by the bit-flipping attack
+ */
+pub struct BitFlip {
+    pub ciphertext: Vec<u8>,
+    // plaintext block which decrypts to garbage, none if only the IV was modified
+    pub scrambled_block: Option<usize>,
+}
+
+/**
+ * CBC bit-flipping: P_i = D(C_i) xor C_(i-1), so xoring C_(i-1) with known xor target
+ * turns the known plaintext of block i into the target
+ * The modified C_(i-1) decrypts to garbage, unless it is the IV
+ * The segment starts at the offset within plaintext block `block` (0 is the first block after the IV)
+ */
+pub fn flip(ciphertext: &[u8], block: usize, offset: usize, known: &[u8], target: &[u8]) -> Result<BitFlip, String> {
+    if known.len() != target.len() {
+        return Err(format!("known plaintext has {} bytes but the target has {}", known.len(), target.len()));
+    }
+    if offset + known.len() > 16 {
+        return Err(format!("segment of {} bytes at offset {} does not fit into one block", known.len(), offset));
+    }
+    let n_blocks = ciphertext.len() / 16 - 1;
+    if block >= n_blocks {
+        return Err(format!("block {} does not exist, the ciphertext has {} blocks", block, n_blocks));
+    }
+
+    // the previous ciphertext block (the IV for block 0) starts at 16 * block in the IV-prefixed ciphertext
+    let mut modified = ciphertext.to_vec();
+    let start = 16 * block + offset;
+    for (i, (known_byte, target_byte)) in known.iter().zip(target).enumerate() {
+        modified[start + i] ^= known_byte ^ target_byte;
+    }
+
+    Ok(BitFlip {
+        ciphertext: modified,
+        scrambled_block: block.checked_sub(1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{decrypt, encrypt};
+    use super::*;
+
+    const KEY: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+    const IV: [u8; 16] = [0xf0; 16];
+    const MESSAGE: &[u8] = b"comment1=cooking%20MCs;userdata=xxxxxxxxxxxxxxxx;comment2=%20like%20a%20pound%20of%20bacon";
+
+    /**
+     * Flips the segment of the message at block and offset, checks that the target appears
+     * and that no block other than the reported one changed
+     */
+    fn check(block: usize, offset: usize, target: &[u8]) {
+        let ciphertext = encrypt(&KEY, &IV, MESSAGE);
+        let start = 16 * block + offset;
+        let known = &MESSAGE[start..start + target.len()];
+        let flipped = flip(&ciphertext, block, offset, known, target).unwrap();
+
+        let original = decrypt(&KEY, &ciphertext).unwrap();
+        let modified = decrypt(&KEY, &flipped.ciphertext).unwrap();
+        assert_eq!(&modified[start..start + target.len()], target);
+
+        let changed_blocks: Vec<usize> = original.chunks(16)
+            .zip(modified.chunks(16))
+            .enumerate()
+            .filter(|(index, (x, y))| x != y && *index != block)
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(changed_blocks, flipped.scrambled_block.into_iter().collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn targets() {
+        check(2, 0, b";admin=true;");
+        check(2, 4, b";admin=true;");
+        check(0, 0, b"role=admin;");
+        check(5, 0, b"x");
+    }
+
+    #[test]
+    fn invalid_segments() {
+        let ciphertext = encrypt(&KEY, &IV, MESSAGE);
+        assert!(flip(&ciphertext, 2, 0, b"abc", b"ab").is_err());
+        assert!(flip(&ciphertext, 2, 10, b"abcdefgh", b"12345678").is_err());
+        assert!(flip(&ciphertext, 6, 0, b"a", b"b").is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::cipher::Cipher;
+use crate::xor_bytes;
+
+/**
+ * Ciphertext stealing variants of NIST SP 800-38A Addendum
+ * They differ only in the order of the last two ciphertext blocks:
+ * CS1 keeps the CBC order, CS3 always swaps them, CS2 swaps them only if the last block is partial
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum CtsVariant {
+    Cs1,
+    Cs2,
+    Cs3,
+}
+
+/**
+ * Encrypts whole blocks in CBC mode without padding, returns the ciphertext without the IV
+ */
+fn cbc_encrypt_blocks(cipher: &Cipher, iv: &[u8], blocks: &[u8]) -> Vec<u8> {
+    let mut ciphertext: Vec<u8> = Vec::new();
+    let mut previous_block = iv.to_vec();
+    for block in blocks.chunks(16) {
+        let mut ciphertext_block = xor_bytes(block, &previous_block);
+        cipher.encrypt_block(&mut ciphertext_block);
+        ciphertext.extend(&ciphertext_block);
+        previous_block = ciphertext_block;
+    }
+    ciphertext
+}
+
+/**
+ * Decrypts whole CBC blocks, returns the plaintext
+ */
+fn cbc_decrypt_blocks(cipher: &Cipher, iv: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    let mut message: Vec<u8> = Vec::new();
+    let mut previous_block = iv;
+    for block in ciphertext.chunks(16) {
+        let mut decrypted_block = block.to_vec();
+        cipher.decrypt_block(&mut decrypted_block);
+        message.extend(xor_bytes(&decrypted_block, previous_block));
+        previous_block = block;
+    }
+    message
+}
+
+/**
+ * Length of the last (possibly partial) block, between 1 and 16 bytes
+ */
+fn last_block_length(length: usize) -> usize {
+    (length - 1) % 16 + 1
+}
+
+/**
+ * Swaps the penultimate block (last_length bytes) and the last block (16 bytes) of the CS1 order for CS2 and CS3,
+ * or swaps them back if to_cs1 is set
+ */
+fn reorder(ciphertext: &[u8], variant: CtsVariant, to_cs1: bool) -> Vec<u8> {
+    let last_length = last_block_length(ciphertext.len());
+    let swap = match variant {
+        CtsVariant::Cs1 => false,
+        CtsVariant::Cs2 => last_length != 16,
+        CtsVariant::Cs3 => true,
+    };
+    if !swap || ciphertext.len() <= 16 {
+        return ciphertext.to_vec();
+    }
+
+    // CS1 ends with C*_(n-1) || C_n, the swapped order with C_n || C*_(n-1)
+    let first_length = match to_cs1 {
+        true => 16,
+        false => last_length,
+    };
+    let tail_start = ciphertext.len() - last_length - 16;
+    let (head, tail) = ciphertext.split_at(tail_start);
+    let mut reordered = head.to_vec();
+    reordered.extend(&tail[first_length..]);
+    reordered.extend(&tail[..first_length]);
+    reordered
+}
+
+/**
+ * Encrypts the message (at least 16 bytes) with ciphertext stealing, returns the IV-prefixed ciphertext
+ * The ciphertext (without the IV) has the same length as the message
+ */
+pub fn encrypt(key: &[u8], iv: &[u8], message: &[u8], variant: CtsVariant) -> Result<Vec<u8>, String> {
+    if message.len() < 16 {
+        return Err(format!("ciphertext stealing needs at least 16 bytes of plaintext, got {}", message.len()));
+    }
+    let cipher = Cipher::new(key);
+
+    // CBC of the zero padded message, the penultimate block is truncated to the length of the last one
+    let last_length = last_block_length(message.len());
+    let mut padded = message.to_vec();
+    padded.extend(vec![0u8; 16 - last_length]);
+    let mut ciphertext = cbc_encrypt_blocks(&cipher, iv, &padded);
+    if ciphertext.len() > 16 {
+        let penultimate_end = ciphertext.len() - 16;
+        ciphertext.drain(penultimate_end - (16 - last_length)..penultimate_end);
+    }
+
+    let mut iv_ciphertext = iv.to_vec();
+    iv_ciphertext.extend(reorder(&ciphertext, variant, false));
+    Ok(iv_ciphertext)
+}
+
+/**
+ * Decrypts an IV-prefixed ciphertext (at least 16 bytes after the IV) encrypted with ciphertext stealing
+ */
+pub fn decrypt(key: &[u8], ciphertext: &[u8], variant: CtsVariant) -> Result<Vec<u8>, String> {
+    if ciphertext.len() < 32 {
+        return Err(format!("ciphertext stealing needs the IV and at least 16 bytes of ciphertext, got {}",
+            ciphertext.len()));
+    }
+    let cipher = Cipher::new(key);
+    let (iv, ciphertext) = ciphertext.split_at(16);
+    let ciphertext = reorder(ciphertext, variant, true);
+
+    let last_length = last_block_length(ciphertext.len());
+    if ciphertext.len() == 16 || last_length == 16 {
+        return Ok(cbc_decrypt_blocks(&cipher, iv, &ciphertext));
+    }
+
+    // C_n = E(C_(n-1) xor (P_n || 0)), so D(C_n) holds P_n xored with C*_(n-1) and the stolen tail of C_(n-1)
+    let stolen_start = ciphertext.len() - 16 - last_length;
+    let (head, tail) = ciphertext.split_at(stolen_start);
+    let (truncated_block, last_block) = tail.split_at(last_length);
+
+    let mut decrypted_last = last_block.to_vec();
+    cipher.decrypt_block(&mut decrypted_last);
+    let mut penultimate_block = truncated_block.to_vec();
+    penultimate_block.extend(&decrypted_last[last_length..]);
+
+    let mut full_blocks = head.to_vec();
+    full_blocks.extend(&penultimate_block);
+    let mut message = cbc_decrypt_blocks(&cipher, iv, &full_blocks);
+    message.extend(xor_bytes(&decrypted_last[..last_length], truncated_block));
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::{self, Mode};
+    use crate::random_bytes;
+    use super::*;
+
+    const VARIANTS: [CtsVariant; 3] = [CtsVariant::Cs1, CtsVariant::Cs2, CtsVariant::Cs3];
+
+    #[test]
+    fn rfc3962() {
+        // the RFC 3962 vectors in the three orders: 17, 31, 32, 48 and 64 bytes
+        let messages = input::read_input(&["fixtures/cts.json".to_string()], None, Mode::Cbc).unwrap();
+        assert_eq!(messages.len(), 15);
+        for message in messages {
+            let variant = match message.mode {
+                Mode::Cts(variant) => variant,
+                _ => panic!("fixture is not in a ciphertext stealing mode"),
+            };
+            let expected = message.expected_plaintext.unwrap();
+            assert_eq!(decrypt(&message.key, &message.ciphertext, variant).unwrap(), expected.as_bytes());
+            assert_eq!(encrypt(&message.key, &message.ciphertext[..16], expected.as_bytes(), variant).unwrap(), message.ciphertext);
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let key = random_bytes(16);
+        let iv = random_bytes(16);
+        for length in 16..=80 {
+            let message = random_bytes(length);
+            for variant in VARIANTS {
+                let ciphertext = encrypt(&key, &iv, &message, variant).unwrap();
+                assert_eq!(ciphertext.len(), 16 + length);
+                assert_eq!(decrypt(&key, &ciphertext, variant).unwrap(), message);
+
+                // whole blocks are plain CBC (without the padding block) in every variant but CS3
+                if length % 16 == 0 && variant != CtsVariant::Cs3 {
+                    assert_eq!(ciphertext, crate::encrypt(&key, &iv, &message)[..16 + length]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn short_inputs() {
+        for variant in VARIANTS {
+            assert!(encrypt(&[0u8; 16], &[0u8; 16], &[0u8; 15], variant).is_err());
+            assert!(decrypt(&[0u8; 16], &[0u8; 31], variant).is_err());
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::cipher::Cipher;
+use crate::pad;
+
+/**
+ * Encrypts the (PKCS#7 padded) message in ECB mode: every block independently under the same key
+ * Equal plaintext blocks give equal ciphertext blocks
+ */
+pub fn encrypt(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let cipher = Cipher::new(key);
+    let mut ciphertext = pad(message);
+    for block in ciphertext.chunks_mut(16) {
+        cipher.encrypt_block(block);
+    }
+    ciphertext
+}
+
+/**
+ * Decrypts ECB ciphertext of whole blocks, the padding is kept
+ */
+pub fn decrypt(key: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    let cipher = Cipher::new(key);
+    let mut message = ciphertext.to_vec();
+    for block in message.chunks_mut(16) {
+        cipher.decrypt_block(block);
+    }
+    message
+}
+
+/**
+ * Checks whether any 16 byte block occurs more than once
+ */
+pub fn has_repeated_blocks(ciphertext: &[u8]) -> bool {
+    let blocks: Vec<&[u8]> = ciphertext.chunks(16).collect();
+    blocks.iter()
+        .enumerate()
+        .any(|(i, x)| blocks[i + 1..].contains(x))
+}
@@ -0,0 +1,253 @@
+use crate::{ecb, encrypt, random_bytes};
+
+// enough equal bytes to fill two whole blocks behind a prefix of any length
+const DETECTION_INPUT_LENGTH: usize = 48;
+
+// fill bytes measuring the prefix length, the later ones are only used while the earlier ones disagree
+const PREFIX_FILL_BYTES: &[u8] = b"ABCD";
+
+/**
+ * Block cipher mode chosen by the detection oracle
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OracleMode {
+    Ecb,
+    Cbc,
+}
+
+/**
+ * Random byte in the range [min, max]
+ */
+fn random_in_range(min: usize, max: usize) -> usize {
+    let bytes = random_bytes(8);
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes);
+    min + (u64::from_le_bytes(value) as usize) % (max - min + 1)
+}
+
+/**
+ * Encryption oracle which encrypts 5-10 random bytes || input || 5-10 random bytes
+ * under a random key, with ECB or CBC (random IV) chosen at random
+ */
+pub struct DetectionOracle {
+    key: Vec<u8>,
+    mode: OracleMode,
+}
+
+impl DetectionOracle {
+
+    pub fn new() -> DetectionOracle {
+        let mode = match random_bytes(1)[0] & 1 {
+            0 => OracleMode::Ecb,
+            _ => OracleMode::Cbc,
+        };
+        DetectionOracle { key: random_bytes(16), mode }
+    }
+
+    /**
+     * The mode actually used, to check the guess of the detector
+     */
+    pub fn mode(&self) -> OracleMode {
+        self.mode
+    }
+
+    pub fn encrypt(&self, input: &[u8]) -> Vec<u8> {
+        let mut message = random_bytes(random_in_range(5, 10));
+        message.extend(input);
+        message.extend(random_bytes(random_in_range(5, 10)));
+
+        match self.mode {
+            OracleMode::Ecb => ecb::encrypt(&self.key, &message),
+            OracleMode::Cbc => encrypt(&self.key, &random_bytes(16), &message),
+        }
+    }
+}
+
+/**
+ * Distinguishes ECB from CBC: equal input blocks give equal ciphertext blocks only in ECB
+ */
+pub fn detect_mode(oracle: &DetectionOracle) -> OracleMode {
+    match ecb::has_repeated_blocks(&oracle.encrypt(&[0u8; DETECTION_INPUT_LENGTH])) {
+        true => OracleMode::Ecb,
+        false => OracleMode::Cbc,
+    }
+}
+
+/**
+ * ECB oracle encrypting prefix || input || secret under a fixed random key
+ * The prefix (possibly empty) and the secret are unknown to the attacker
+ */
+pub struct SuffixOracle {
+    key: Vec<u8>,
+    prefix: Vec<u8>,
+    secret: Vec<u8>,
+    n_queries: usize,
+}
+
+impl SuffixOracle {
+
+    pub fn new(prefix: &[u8], secret: &[u8]) -> SuffixOracle {
+        SuffixOracle { key: random_bytes(16), prefix: prefix.to_vec(), secret: secret.to_vec(), n_queries: 0 }
+    }
+
+    pub fn encrypt(&mut self, input: &[u8]) -> Vec<u8> {
+        self.n_queries += 1;
+        let mut message = self.prefix.clone();
+        message.extend(input);
+        message.extend(&self.secret);
+        ecb::encrypt(&self.key, &message)
+    }
+
+    pub fn n_queries(&self) -> usize {
+        self.n_queries
+    }
+}
+
+/**
+ * Block size: the ciphertext grows by one whole block once the input fills the last one
+ */
+fn detect_block_size(oracle: &mut SuffixOracle) -> Result<usize, String> {
+    let initial_length = oracle.encrypt(&[]).len();
+    (1..=64)
+        .map(|n| oracle.encrypt(&vec![0u8; n]).len() - initial_length)
+        .find(|x| *x > 0)
+        .ok_or_else(|| "ciphertext length does not depend on the input".to_string())
+}
+
+/**
+ * Index of the first block equal to the following one which is not already in the reference ciphertext
+ * Blocks entirely inside the prefix are the same in every ciphertext, so equal blocks of the prefix itself are skipped
+ */
+fn first_new_repeated_pair(ciphertext: &[u8], reference: &[u8], block_size: usize) -> Option<usize> {
+    let blocks: Vec<&[u8]> = ciphertext.chunks(block_size).collect();
+    let reference: Vec<&[u8]> = reference.chunks(block_size).collect();
+    (0..blocks.len().saturating_sub(1))
+        .find(|x| blocks[*x] == blocks[x + 1] && reference.get(*x) != Some(&blocks[*x]))
+}
+
+/**
+ * Prefix length for the given fill byte: k filler bytes followed by two blocks of the fill byte
+ * first produce two new equal blocks (compared to the reference, encrypted without the fill) when prefix + k reaches a block boundary
+ */
+fn prefix_length_with(oracle: &mut SuffixOracle, block_size: usize, reference: &[u8], fill: u8) -> Option<usize> {
+    (0..block_size).find_map(|k| {
+        let mut input = vec![fill ^ 0xff; k];
+        input.extend(vec![fill; 2 * block_size]);
+        first_new_repeated_pair(&oracle.encrypt(&input), reference, block_size).map(|x| x * block_size - k)
+    })
+}
+
+/**
+ * Length of the random prefix
+ * A single fill byte can be wrong either way: when the partial last block of the prefix is made of the fill byte
+ * the equal blocks appear with fewer filler bytes (underestimating it), and a secret starting with the fill byte
+ * makes them appear later. At most two fill bytes are wrong, in opposite directions, so the first length
+ * two fill bytes agree on is right.
+ */
+fn detect_prefix_length(oracle: &mut SuffixOracle, block_size: usize) -> Result<usize, String> {
+    let reference = oracle.encrypt(&[]);
+    let mut estimates: Vec<usize> = Vec::new();
+    for fill in PREFIX_FILL_BYTES {
+        let estimate = prefix_length_with(oracle, block_size, &reference, *fill)
+            .ok_or("no repeated blocks, the oracle does not use ECB")?;
+        if estimates.contains(&estimate) {
+            return Ok(estimate);
+        }
+        estimates.push(estimate);
+    }
+    Err("fill bytes disagree on the prefix length".to_string())
+}
+
+/**
+ * Byte-at-a-time ECB decryption of the secret suffix
+ * The input aligns the next unknown byte to the end of a block whose other bytes are known,
+ * the byte is found by comparing the block with encryptions of all 256 candidates
+ */
+pub fn recover_secret(oracle: &mut SuffixOracle) -> Result<Vec<u8>, String> {
+    let block_size = detect_block_size(oracle)?;
+    if !ecb::has_repeated_blocks(&oracle.encrypt(&vec![0u8; 3 * block_size])) {
+        return Err("no repeated blocks, the oracle does not use ECB".to_string());
+    }
+    let prefix_length = detect_prefix_length(oracle, block_size)?;
+
+    // filler completing the last prefix block, the attacker controlled blocks start after it
+    let alignment = (block_size - prefix_length % block_size) % block_size;
+    let first_block = (prefix_length + alignment) / block_size;
+
+    // the padding grows by a whole block when prefix + input + secret reaches a block boundary
+    let initial_length = oracle.encrypt(&vec![0u8; alignment]).len();
+    let extra = (1..=block_size)
+        .find(|x| oracle.encrypt(&vec![0u8; alignment + x]).len() > initial_length)
+        .ok_or("unable to determine the secret length")?;
+    let secret_length = initial_length - (prefix_length + alignment) - extra;
+
+    let mut secret: Vec<u8> = Vec::new();
+    while secret.len() < secret_length {
+        let n_filler = block_size - 1 - secret.len() % block_size;
+        let mut input = vec![0u8; alignment + n_filler];
+        let target_block = first_block + secret.len() / block_size;
+        let ciphertext = oracle.encrypt(&input);
+        let target = &ciphertext[target_block * block_size..(target_block + 1) * block_size];
+
+        // the block to match: the last block_size - 1 known bytes (filler and recovered secret) and the candidate
+        input.extend(&secret);
+        let known = input[input.len() - (block_size - 1)..].to_vec();
+        let byte = (0..=255u8)
+            .find(|x| {
+                let mut candidate = vec![0u8; alignment];
+                candidate.extend(&known);
+                candidate.push(*x);
+                let ciphertext = oracle.encrypt(&candidate);
+                ciphertext[first_block * block_size..(first_block + 1) * block_size] == *target
+            })
+            .ok_or_else(|| format!("no candidate matches byte {} of the secret", secret.len()))?;
+        secret.push(byte);
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"AAAA secret suffix starting with the fill byte";
+
+    fn recover_with_prefix(prefix: &[u8]) -> Vec<u8> {
+        recover_secret(&mut SuffixOracle::new(prefix, SECRET)).unwrap()
+    }
+
+    #[test]
+    fn prefix_of_fill_bytes() {
+        for prefix in [&b""[..], b"A", b"B", &[b'A'; 17], &[b'B'; 31], b"xxxxxxxxxxxxxxxAB"] {
+            assert_eq!(recover_with_prefix(prefix), SECRET, "prefix {:?}", prefix);
+        }
+    }
+
+    #[test]
+    fn prefix_with_repeated_blocks() {
+        let random_block = random_bytes(16);
+        let mut repeated = random_block.clone();
+        repeated.extend(&random_block);
+        repeated.extend(random_bytes(5));
+
+        for prefix in [&[b'x'; 32][..], &[b'x'; 40], &[b'A'; 35], &repeated] {
+            assert_eq!(recover_with_prefix(prefix), SECRET, "prefix {:?}", prefix);
+        }
+    }
+
+    #[test]
+    fn random_prefixes() {
+        for _ in 0..200 {
+            let prefix = random_bytes(random_in_range(0, 40));
+            assert_eq!(recover_with_prefix(&prefix), SECRET, "prefix {:?}", prefix);
+        }
+    }
+
+    #[test]
+    fn detection() {
+        for _ in 0..500 {
+            let oracle = DetectionOracle::new();
+            assert_eq!(detect_mode(&oracle), oracle.mode());
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use cipher_cli::kdf::hkdf_sha256;
+use crate::{decrypt, encrypt, unpad};
+
+pub const TAG_LENGTH: usize = 32;
+
+// HKDF labels separating the two keys derived from the master key
+const ENCRYPTION_KEY_INFO: &[u8] = b"cbc-decryption etm aes-128-cbc";
+const MAC_KEY_INFO: &[u8] = b"cbc-decryption etm hmac-sha256";
+
+/**
+ * Reasons for rejecting a container, in the order in which they are checked
+ */
+#[derive(Debug, PartialEq)]
+pub enum EtmError {
+    BadLength(usize),
+    TagMismatch,
+    BadPadding,
+}
+
+impl fmt::Display for EtmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EtmError::BadLength(length) => write!(f, "container of {} bytes is not IV || ciphertext blocks || tag", length),
+            EtmError::TagMismatch => write!(f, "authentication tag does not match"),
+            EtmError::BadPadding => write!(f, "authenticated plaintext has invalid padding"),
+        }
+    }
+}
+
+/**
+ * Encryption and MAC keys derived from one master key
+ */
+pub struct EtmKeys {
+    encryption_key: Vec<u8>,
+    mac_key: Vec<u8>,
+}
+
+impl EtmKeys {
+
+    /**
+     * Derives the AES-128 key and the HMAC-SHA256 key from the master key (at least 16 bytes) with HKDF
+     */
+    pub fn derive(master_key: &[u8]) -> Result<EtmKeys, String> {
+        if master_key.len() < 16 {
+            return Err(format!("master key must be at least 16 bytes long, got {}", master_key.len()));
+        }
+        Ok(EtmKeys {
+            encryption_key: hkdf_sha256(master_key, &[], ENCRYPTION_KEY_INFO, 16),
+            mac_key: hkdf_sha256(master_key, &[], MAC_KEY_INFO, 32),
+        })
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::<Sha256>::new_from_slice(&self.mac_key).expect("HMAC accepts keys of any length")
+    }
+
+    /**
+     * Encrypts the message with AES-128-CBC and appends HMAC-SHA256 of IV || ciphertext
+     * Returns the container IV || ciphertext || tag
+     */
+    pub fn seal(&self, iv: &[u8], message: &[u8]) -> Vec<u8> {
+        let mut container = encrypt(&self.encryption_key, iv, message);
+
+        let mut mac = self.mac();
+        mac.update(&container);
+        container.extend(mac.finalize().into_bytes());
+        container
+    }
+
+    /**
+     * Checks the tag in constant time and only then decrypts and removes the padding
+     * A tampered container is rejected before the ciphertext is decrypted, so it can not serve as a padding oracle
+     */
+    pub fn open(&self, container: &[u8]) -> Result<Vec<u8>, EtmError> {
+        let length = container.len();
+        if length < 32 + TAG_LENGTH || !(length - TAG_LENGTH).is_multiple_of(16) {
+            return Err(EtmError::BadLength(length));
+        }
+
+        let (iv_ciphertext, tag) = container.split_at(length - TAG_LENGTH);
+        let mut mac = self.mac();
+        mac.update(iv_ciphertext);
+        mac.verify_slice(tag).map_err(|_| EtmError::TagMismatch)?;
+
+        let plaintext = decrypt(&self.encryption_key, iv_ciphertext).map_err(|_| EtmError::BadLength(length))?;
+        unpad(&plaintext).map(|x| x.to_vec()).ok_or(EtmError::BadPadding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::random_bytes;
+    use super::*;
+
+    fn keys_and_container() -> (EtmKeys, Vec<u8>) {
+        let keys = EtmKeys::derive(&random_bytes(32)).unwrap();
+        let container = keys.seal(&random_bytes(16), b"Encrypt-then-MAC: the tag covers the IV and every ciphertext block.");
+        (keys, container)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (keys, container) = keys_and_container();
+        assert_eq!(keys.open(&container).unwrap(), b"Encrypt-then-MAC: the tag covers the IV and every ciphertext block.");
+        assert!(EtmKeys::derive(&[0u8; 15]).is_err());
+    }
+
+    #[test]
+    fn bit_flips() {
+        // every bit of the IV, of the ciphertext and of the tag
+        let (keys, container) = keys_and_container();
+        for position in 0..container.len() {
+            for bit in 0..8 {
+                let mut tampered = container.clone();
+                tampered[position] ^= 1 << bit;
+                assert_eq!(keys.open(&tampered), Err(EtmError::TagMismatch), "bit {} of byte {}", bit, position);
+            }
+        }
+    }
+
+    #[test]
+    fn lengths() {
+        let (keys, container) = keys_and_container();
+        assert_eq!(keys.open(&container[..container.len() - 1]), Err(EtmError::BadLength(container.len() - 1)));
+        assert_eq!(keys.open(&container[..32 + TAG_LENGTH - 16]), Err(EtmError::BadLength(32 + TAG_LENGTH - 16)));
+
+        // whole blocks removed or added keep a valid length, the tag rejects them
+        assert_eq!(keys.open(&container[16..]), Err(EtmError::TagMismatch));
+        assert_eq!(keys.open(&[&container[..], &[0u8; 16]].concat()), Err(EtmError::TagMismatch));
+    }
+
+    #[test]
+    fn padding_behind_the_tag() {
+        // container with a correct tag over a ciphertext whose plaintext is not padded, reachable only with the keys
+        let (keys, _) = keys_and_container();
+        let mut container = encrypt(&keys.encryption_key, &random_bytes(16), &[0u8; 32]);
+        container.truncate(container.len() - 16);
+        let mut mac = keys.mac();
+        mac.update(&container);
+        container.extend(mac.finalize().into_bytes());
+
+        assert_eq!(keys.open(&container), Err(EtmError::BadPadding));
+    }
+}
@@ -0,0 +1,64 @@
+use crate::mac::{self, MacAlgorithm};
+use crate::xor_bytes;
+
+/**
+ * Result of the forgery against one MAC algorithm
+ */
+pub struct Forgery {
+    pub message: Vec<u8>,
+    pub tag: Vec<u8>,
+    pub verified: bool,
+}
+
+/**
+ * Length-extension forgery: from tags t1 of m1 and t2 of m2 (obtained from a MAC oracle)
+ * the message m1 || (m2[0] xor t1) || m2[1..] gets tag t2 under raw CBC-MAC without querying it:
+ * the xor with t1 cancels the chaining value, so CBC continues exactly as when m2 was processed alone
+ * CMAC masks the last block with a secret subkey, so the tag t1 is not the chaining value and the forgery fails
+ */
+pub fn forge(algorithm: MacAlgorithm, key: &[u8], message_1: &[u8], message_2: &[u8]) -> Result<Forgery, String> {
+    if message_2.len() < 16 {
+        return Err("the second message must contain at least one block".to_string());
+    }
+
+    // oracle queries
+    let tag_1 = mac::tag(algorithm, key, message_1)?;
+    let tag_2 = mac::tag(algorithm, key, message_2)?;
+
+    let mut message = message_1.to_vec();
+    message.extend(xor_bytes(&message_2[0..16], &tag_1));
+    message.extend(&message_2[16..]);
+
+    let verified = mac::verify(algorithm, key, &message, &tag_2);
+    Ok(Forgery { message, tag: tag_2, verified })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::random_bytes;
+    use super::*;
+
+    const MESSAGE_1: &[u8] = b"To: bank        Pay Alice $100  ";
+    const MESSAGE_2: &[u8] = b"Pay Mallory $1000000, approved. ";
+
+    #[test]
+    fn cbc_mac_accepts_cmac_rejects() {
+        for _ in 0..20 {
+            let key = random_bytes(16);
+
+            let forgery = forge(MacAlgorithm::CbcMac, &key, MESSAGE_1, MESSAGE_2).unwrap();
+            assert!(forgery.verified);
+            assert_eq!(forgery.message.len(), MESSAGE_1.len() + MESSAGE_2.len());
+            assert_eq!(mac::tag(MacAlgorithm::CbcMac, &key, &forgery.message).unwrap(), forgery.tag);
+
+            let forgery = forge(MacAlgorithm::Cmac, &key, MESSAGE_1, MESSAGE_2).unwrap();
+            assert!(!forgery.verified);
+            assert!(!mac::verify(MacAlgorithm::Cmac, &key, &forgery.message, &forgery.tag));
+        }
+    }
+
+    #[test]
+    fn short_second_message() {
+        assert!(forge(MacAlgorithm::CbcMac, &[0u8; 16], MESSAGE_1, &MESSAGE_2[..15]).is_err());
+    }
+}
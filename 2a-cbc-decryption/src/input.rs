@@ -0,0 +1,198 @@
+use cipher_cli::input::{self, decode_field, Record};
+use cipher_cli::kdf::{KeyDerivation, OPENSSL_MAGIC};
+use crate::cts::CtsVariant;
+
+/**
+ * Block cipher mode of a message: plain CBC with PKCS#7 padding, CBC with ciphertext stealing
+ * or ECB (PKCS#7 padding, no IV), which is only supported for analysis of legacy data
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    Cbc,
+    Cts(CtsVariant),
+    Ecb,
+}
+
+impl Mode {
+
+    /**
+     * Extracts --mode <cbc|cbc-cs1|cbc-cs2|cbc-cs3|ecb> from the arguments
+     * Returns the mode (cbc by default) and the remaining arguments
+     */
+    pub fn from_args(args: &[String]) -> Result<(Mode, Vec<String>), String> {
+        let mut mode = Mode::Cbc;
+        let mut remaining: Vec<String> = Vec::new();
+
+        let mut args_iter = args.iter();
+        while let Some(arg) = args_iter.next() {
+            match arg.as_str() {
+                "--mode" => mode = Mode::from_name(args_iter.next().ok_or("--mode requires a value")?)?,
+                _ => remaining.push(arg.clone()),
+            }
+        }
+        Ok((mode, remaining))
+    }
+
+    /**
+     * Parses cbc, cbc-cs1, cbc-cs2, cbc-cs3 or ecb (case insensitive)
+     */
+    pub fn from_name(name: &str) -> Result<Mode, String> {
+        match name.to_lowercase().as_str() {
+            "cbc" => Ok(Mode::Cbc),
+            "cbc-cs1" => Ok(Mode::Cts(CtsVariant::Cs1)),
+            "cbc-cs2" => Ok(Mode::Cts(CtsVariant::Cs2)),
+            "cbc-cs3" => Ok(Mode::Cts(CtsVariant::Cs3)),
+            "ecb" => Ok(Mode::Ecb),
+            _ => Err(format!("mode {} is not supported, expected cbc, cbc-cs1, cbc-cs2, cbc-cs3 or ecb", name)),
+        }
+    }
+}
+
+/**
+ * Validated message ready for decryption
+ */
+pub struct Message {
+    pub key: Vec<u8>,
+    // IV-prefixed ciphertext (no IV in ECB mode)
+    pub ciphertext: Vec<u8>,
+    pub expected_plaintext: Option<String>,
+    pub mode: Mode,
+}
+
+/**
+ * Checks lengths of a decoded message
+ */
+fn validate(message: &Message, index: usize) -> Result<(), String> {
+    if message.key.len() != 16 {
+        return Err(format!("record {}: key must be 16 bytes long, got {}", index, message.key.len()));
+    }
+    let length = message.ciphertext.len();
+    match message.mode {
+        Mode::Cbc if length < 32 || !length.is_multiple_of(16) =>
+            Err(format!("record {}: IV and ciphertext must be whole 16 byte blocks (at least 2), got {} bytes",
+                index, length)),
+        Mode::Cts(_) if length < 32 =>
+            Err(format!("record {}: IV and ciphertext must be at least 32 bytes long, got {}", index, length)),
+        Mode::Ecb if length < 16 || !length.is_multiple_of(16) =>
+            Err(format!("record {}: ciphertext must be whole 16 byte blocks (at least 1), got {} bytes",
+                index, length)),
+        _ => Ok(()),
+    }
+}
+
+/**
+ * Validates a record and converts it into a message, records without a mode use the default one
+ */
+fn record_to_message(
+    record: Record,
+    index: usize,
+    key_derivation: Option<&KeyDerivation>,
+    default_mode: Mode
+) -> Result<Message, String> {
+    let mode = match &record.mode {
+        Some(mode) => Mode::from_name(mode).map_err(|x| format!("record {}: {}", index, x))?,
+        None => default_mode,
+    };
+
+    let mut ciphertext = match &record.iv {
+        Some(_) if mode == Mode::Ecb => return Err(format!("record {}: ecb mode takes no iv", index)),
+        Some(iv) => {
+            let iv = decode_field(iv, record.encoding, "iv", index)?;
+            if iv.len() != 16 {
+                return Err(format!("record {}: iv must be 16 bytes long, got {}", index, iv.len()));
+            }
+            iv
+        }
+        None => Vec::new(),
+    };
+    ciphertext.extend(decode_field(&record.ciphertext, record.encoding, "ciphertext", index)?);
+
+    // the passphrase takes precedence over the key of the record
+    let (key, ciphertext) = match (key_derivation, &record.key) {
+        (Some(key_derivation), _) => {
+            let (key, derived) = key_derivation.derive_message_key(&ciphertext)
+                .map_err(|x| format!("record {}: {}", index, x))?;
+
+            // the IV derived from an OpenSSL header is not used in ECB mode
+            match mode == Mode::Ecb && ciphertext.starts_with(OPENSSL_MAGIC) {
+                true => (key, derived[16..].to_vec()),
+                false => (key, derived),
+            }
+        }
+        (None, Some(key)) => (decode_field(key, record.encoding, "key", index)?, ciphertext),
+        (None, None) => return Err(format!("record {}: key is missing and no passphrase was given", index)),
+    };
+
+    let message = Message { key, ciphertext, expected_plaintext: record.expected_plaintext, mode };
+    validate(&message, index)?;
+    Ok(message)
+}
+
+/**
+ * Reads either the structured input (one path) or the legacy input (two paths), see `cipher_cli::input::read_input`
+ * The mode applies to legacy lines and records without a mode field
+ */
+pub fn read_input(paths: &[String], key_derivation: Option<&KeyDerivation>, mode: Mode) -> Result<Vec<Message>, String> {
+    input::read_input(paths, key_derivation, |record, index, key_derivation| {
+        record_to_message(record, index, key_derivation, mode)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use cipher_cli::input::Encoding;
+    use super::*;
+
+    fn record(key: &str, iv: Option<&str>, ciphertext: &str, mode: Option<&str>) -> Record {
+        Record {
+            key: Some(key.to_string()),
+            iv: iv.map(|x| x.to_string()),
+            ciphertext: ciphertext.to_string(),
+            encoding: Encoding::Hex,
+            mode: mode.map(|x| x.to_string()),
+            expected_plaintext: None,
+        }
+    }
+
+    fn error(record: Record) -> String {
+        record_to_message(record, 4, None, Mode::Cbc).err().unwrap()
+    }
+
+    #[test]
+    fn valid_records() {
+        let key = "00".repeat(16);
+        let message = record_to_message(record(&key, Some(&"11".repeat(16)), &"22".repeat(32), None), 0, None, Mode::Cbc).unwrap();
+        assert_eq!((message.key.len(), message.ciphertext.len()), (16, 48));
+        assert!(message.mode == Mode::Cbc);
+
+        // ciphertext stealing takes partial blocks, ECB has no IV
+        let message = record_to_message(record(&key, None, &"22".repeat(33), Some("CBC-CS3")), 0, None, Mode::Cbc).unwrap();
+        assert!(message.mode == Mode::Cts(CtsVariant::Cs3));
+        let message = record_to_message(record(&key, None, &"22".repeat(16), None), 0, None, Mode::Ecb).unwrap();
+        assert!(message.mode == Mode::Ecb);
+    }
+
+    #[test]
+    fn invalid_records() {
+        let key = "00".repeat(16);
+        let iv = "11".repeat(16);
+        assert_eq!(error(record(&"00".repeat(24), Some(&iv), &"22".repeat(16), None)), "record 4: key must be 16 bytes long, got 24");
+        assert_eq!(error(record(&key, Some(&"11".repeat(8)), &"22".repeat(16), None)), "record 4: iv must be 16 bytes long, got 8");
+        assert_eq!(error(record(&key, Some(&iv), &"22".repeat(17), None)),
+            "record 4: IV and ciphertext must be whole 16 byte blocks (at least 2), got 33 bytes");
+        assert_eq!(error(record(&key, None, &"22".repeat(16), None)),
+            "record 4: IV and ciphertext must be whole 16 byte blocks (at least 2), got 16 bytes");
+        assert_eq!(error(record(&key, None, &"22".repeat(31), Some("cbc-cs1"))),
+            "record 4: IV and ciphertext must be at least 32 bytes long, got 31");
+        assert_eq!(error(record(&key, Some(&iv), &"22".repeat(16), Some("ecb"))), "record 4: ecb mode takes no iv");
+        assert_eq!(error(record(&key, None, &"22".repeat(15), Some("ecb"))),
+            "record 4: ciphertext must be whole 16 byte blocks (at least 1), got 15 bytes");
+        assert!(error(record(&key, Some(&iv), &"22".repeat(16), Some("ofb"))).starts_with("record 4: mode ofb is not supported"));
+        assert!(error(record(&"0g".repeat(16), Some(&iv), &"22".repeat(16), None)).starts_with("record 4: key is not valid"));
+        assert!(error(record(&key, Some(&iv), "223", None)).starts_with("record 4: ciphertext is not valid"));
+
+        let mut keyless = record(&key, Some(&iv), &"22".repeat(16), None);
+        keyless.key = None;
+        assert_eq!(error(keyless), "record 4: key is missing and no passphrase was given");
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{decrypt, encrypt, unpad, xor_bytes};
+
+// message encrypted by the legacy system in the demonstration, three blocks and more
+pub const EXAMPLE_MESSAGE: &[u8] = b"Legacy system message: the IV of this ciphertext is the key.";
+
+/**
+ * Legacy system using the AES key as the CBC IV, the IV is not transmitted
+ * Decryption reports plaintexts which are not ASCII in its error message
+ */
+pub struct Oracle {
+    key: Vec<u8>,
+    n_queries: usize,
+}
+
+impl Oracle {
+
+    pub fn new(key: &[u8]) -> Oracle {
+        Oracle { key: key.to_vec(), n_queries: 0 }
+    }
+
+    /**
+     * Encrypts the message with IV = key, returns the ciphertext without IV
+     */
+    pub fn encrypt(&self, message: &[u8]) -> Vec<u8> {
+        encrypt(&self.key, &self.key, message)[16..].to_vec()
+    }
+
+    /**
+     * Decrypts the ciphertext (IV = key) and validates the plaintext
+     * Like many legacy systems, the error message includes the offending plaintext
+     */
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<(), String> {
+        self.n_queries += 1;
+        let mut iv_ciphertext = self.key.clone();
+        iv_ciphertext.extend(ciphertext);
+
+        let plaintext = decrypt(&self.key, &iv_ciphertext)?;
+        let message = unpad(&plaintext).ok_or("invalid padding")?;
+        match message.is_ascii() {
+            true => Ok(()),
+            false => Err(format!("invalid characters in message {}", hex::encode(message))),
+        }
+    }
+
+    pub fn n_queries(&self) -> usize {
+        self.n_queries
+    }
+}
+
+/**
+ * Recovers the key from one ciphertext of at least three blocks and usually one decryption query
+ * The ciphertext C1 || M || C1 || C2 ... decrypts to P'1 = D(C1) xor K, P'2 = garbage, P'3 = D(C1) xor M,
+ * so K = P'1 xor P'3 xor M; the original blocks after C1 keep the padding valid
+ */
+pub fn recover_key(oracle: &mut Oracle, ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+    if ciphertext.len() < 48 {
+        return Err(format!("the attack needs a ciphertext of at least 3 blocks, got {} bytes", ciphertext.len()));
+    }
+
+    // P'2 and P'3 are both ASCII (and no plaintext leaks) with probability 2^-32, another M changes both
+    for fill in 0..=255u8 {
+        let middle = [fill; 16];
+        let mut modified = ciphertext[0..16].to_vec();
+        modified.extend(middle);
+        modified.extend(&ciphertext[0..16]);
+        modified.extend(&ciphertext[16..]);
+
+        let error = match oracle.decrypt(&modified) {
+            Ok(()) => continue,
+            Err(error) => error,
+        };
+        let leaked = error.rsplit(' ').next()
+            .and_then(|x| hex::decode(x).ok())
+            .filter(|x| x.len() >= 48)
+            .ok_or(format!("no plaintext in the error message: {}", error))?;
+
+        return Ok(xor_bytes(&xor_bytes(&leaked[0..16], &leaked[32..48]), &middle));
+    }
+    Err("every modified ciphertext was accepted, no plaintext leaked".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cipher::Cipher;
+    use crate::random_bytes;
+    use super::*;
+
+    #[test]
+    fn many_keys() {
+        for _ in 0..500 {
+            let key = random_bytes(16);
+            let mut oracle = Oracle::new(&key);
+            let ciphertext = oracle.encrypt(EXAMPLE_MESSAGE);
+            assert_eq!(recover_key(&mut oracle, &ciphertext).unwrap(), key);
+
+            // a second query only if the first plaintext is ASCII
+            assert!(oracle.n_queries() <= 2);
+        }
+    }
+
+    #[test]
+    fn accepted_first_query() {
+        // a key for which C1 || 0 || C1 ... decrypts to ASCII: with P1 xor K = A ASCII, P'3 = A and C1 = E(A),
+        // P'2 = D(0) xor C1 is ASCII with probability 2^-16
+        let key = loop {
+            let ascii: Vec<u8> = random_bytes(16).iter().map(|x| x & 0x7f).collect();
+            let key = xor_bytes(&EXAMPLE_MESSAGE[0..16], &ascii);
+            let cipher = Cipher::new(&key);
+            let (mut c1, mut p2) = (ascii, [0u8; 16]);
+            cipher.encrypt_block(&mut c1);
+            cipher.decrypt_block(&mut p2);
+            if xor_bytes(&p2, &c1).is_ascii() {
+                break key;
+            }
+        };
+
+        let mut oracle = Oracle::new(&key);
+        let ciphertext = oracle.encrypt(EXAMPLE_MESSAGE);
+        assert_eq!(recover_key(&mut oracle, &ciphertext).unwrap(), key);
+        assert_eq!(oracle.n_queries(), 2);
+    }
+
+    #[test]
+    fn short_ciphertext() {
+        let mut oracle = Oracle::new(&random_bytes(16));
+        let ciphertext = oracle.encrypt(b"two blocks only");
+        assert!(recover_key(&mut oracle, &ciphertext).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::cipher::Cipher;
+use crate::xor_bytes;
+
+// constant of the subkey generation for 128-bit blocks (x^128 = x^7 + x^2 + x + 1)
+const RB: u8 = 0x87;
+
+/**
+ * Message authentication code built on AES
+ */
+#[derive(Clone, Copy)]
+pub enum MacAlgorithm {
+    // CBC encryption with zero IV, the last block is the tag, only secure for messages of one fixed length
+    CbcMac,
+    // CBC-MAC with the last block masked by a key derived subkey (RFC 4493)
+    Cmac,
+}
+
+/**
+ * CBC encryption of whole blocks with zero IV, returns the last ciphertext block
+ */
+fn cbc_last_block(cipher: &Cipher, blocks: &[u8]) -> Vec<u8> {
+    let mut state = vec![0u8; 16];
+    for block in blocks.chunks(16) {
+        state = xor_bytes(&state, block);
+        cipher.encrypt_block(&mut state);
+    }
+    state
+}
+
+/**
+ * Raw CBC-MAC of a message of whole blocks (at least one)
+ */
+pub fn cbc_mac(key: &[u8], message: &[u8]) -> Result<Vec<u8>, String> {
+    if message.is_empty() || !message.len().is_multiple_of(16) {
+        return Err(format!("CBC-MAC needs whole 16 byte blocks, got {} bytes", message.len()));
+    }
+    Ok(cbc_last_block(&Cipher::new(key), message))
+}
+
+/**
+ * Multiplication by x in GF(2^128), the block is a big-endian polynomial
+ */
+fn double(block: &[u8]) -> Vec<u8> {
+    let mut doubled: Vec<u8> = block.iter()
+        .zip(block.iter().skip(1).chain([0u8].iter()))
+        .map(|(x, next)| (x << 1) | (next >> 7))
+        .collect();
+    doubled[15] ^= RB & 0u8.wrapping_sub(block[0] >> 7);
+    doubled
+}
+
+/**
+ * Subkeys K1 and K2 of CMAC (RFC 4493, section 2.3)
+ */
+pub fn cmac_subkeys(key: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut l = vec![0u8; 16];
+    Cipher::new(key).encrypt_block(&mut l);
+    let k1 = double(&l);
+    let k2 = double(&k1);
+    (k1, k2)
+}
+
+/**
+ * AES-CMAC (RFC 4493) of a message of any length
+ * A complete last block is xored with K1, a partial one is padded with 10...0 and xored with K2
+ */
+pub fn cmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let (k1, k2) = cmac_subkeys(key);
+
+    let n_blocks = message.len().div_ceil(16).max(1);
+    let last_start = 16 * (n_blocks - 1);
+    let last_block = match message.len() - last_start {
+        16 => xor_bytes(&message[last_start..], &k1),
+        _ => {
+            let mut padded = message[last_start..].to_vec();
+            padded.push(0x80);
+            padded.resize(16, 0);
+            xor_bytes(&padded, &k2)
+        }
+    };
+
+    let mut blocks = message[..last_start].to_vec();
+    blocks.extend(last_block);
+    cbc_last_block(&Cipher::new(key), &blocks)
+}
+
+/**
+ * Computes the tag of the message with the given algorithm
+ */
+pub fn tag(algorithm: MacAlgorithm, key: &[u8], message: &[u8]) -> Result<Vec<u8>, String> {
+    match algorithm {
+        MacAlgorithm::CbcMac => cbc_mac(key, message),
+        MacAlgorithm::Cmac => Ok(cmac(key, message)),
+    }
+}
+
+/**
+ * Compares two byte strings in time independent of the position of the first difference
+ */
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |x, (a, b)| x | (a ^ b)) == 0
+}
+
+/**
+ * Checks the tag of the message
+ */
+pub fn verify(algorithm: MacAlgorithm, key: &[u8], message: &[u8], expected_tag: &[u8]) -> bool {
+    match tag(algorithm, key, message) {
+        Ok(tag) => constant_time_eq(&tag, expected_tag),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+
+    const RFC4493_KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+
+    #[test]
+    fn rfc4493() {
+        let key = hex::decode(RFC4493_KEY).unwrap();
+        let vectors = [
+            ("fixtures/cmac-0.bin", "bb1d6929e95937287fa37d129b756746"),
+            ("fixtures/cmac-16.bin", "070a16b46b4d4144f79bdd9dd04a287c"),
+            ("fixtures/cmac-40.bin", "dfa66747de9ae63030ca32611497c827"),
+            ("fixtures/cmac-64.bin", "51f0bebf7e3b9d92fc49741779363cfe"),
+        ];
+        for (path, tag) in vectors {
+            let message = fs::read(path).unwrap();
+            assert_eq!(hex::encode(cmac(&key, &message)), tag, "{}", path);
+            assert!(verify(MacAlgorithm::Cmac, &key, &message, &hex::decode(tag).unwrap()));
+        }
+
+        let (k1, k2) = cmac_subkeys(&key);
+        assert_eq!(hex::encode(k1), "fbeed618357133667c85e08f7236a8de");
+        assert_eq!(hex::encode(k2), "f7ddac306ae266ccf90bc11ee46d513b");
+    }
+
+    #[test]
+    fn rejects_other_tags() {
+        let key = hex::decode(RFC4493_KEY).unwrap();
+        let message = fs::read("fixtures/cmac-64.bin").unwrap();
+        let mut tag = cmac(&key, &message);
+        tag[15] ^= 1;
+        assert!(!verify(MacAlgorithm::Cmac, &key, &message, &tag));
+        assert!(!verify(MacAlgorithm::Cmac, &key, &message, &tag[..15]));
+
+        // CBC-MAC takes whole blocks only
+        assert!(cbc_mac(&key, &message[..40]).is_err());
+    }
+}
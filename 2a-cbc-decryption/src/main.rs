@@ -1,94 +1,815 @@
+use std::env;
 use std::fs;
-use aes::Aes128;
-use aes::cipher::{
-    BlockDecrypt, NewBlockCipher,
-    generic_array::GenericArray
-};
-use hex;
+use std::process;
+use cipher_cli::openssl::EncCipher;
+use cipher_cli::{kdf, openssl, output};
+
+mod audit;
+mod bitflip;
+mod cipher;
+mod cts;
+mod ecb;
+mod ecb_attack;
+mod etm;
+mod forgery;
+mod input;
+mod iv_key;
+mod mac;
+
+const USAGE: &str = "usage: cargo run [-- <command> <args>]
+commands:
+    decrypt [--mode cipher_mode] [--output mode] [--out-dir dir] [passphrase options] [input_json | ciphertexts_file keys_file]
+    encrypt [--mode cipher_mode] key_hex [iv_hex] in_file
+    bitflip [--offset n] [--key key_hex] ciphertext_hex block known_text target_text
+    ecb-detect
+    ecb-attack [--prefix] [--trials n] [secret_file]
+    etm-encrypt master_key_hex in_file out_file
+    etm-decrypt [--output mode] [--out-dir dir] master_key_hex in_file
+    iv-key-attack [key_hex]
+    mac [--cmac | --cbc-mac] [--verify tag_hex] key_hex in_file
+    mac-forgery [key_hex]
+    audit [--mode cipher_mode] [input_json | ciphertexts_file keys_file]
+    openssl-decrypt [-a] (-K key_hex -iv iv_hex | passphrase options) [--output mode] [--out-dir dir] in_file
+    openssl-encrypt [-a] (-K key_hex -iv iv_hex | passphrase options) in_file out_file
+cipher modes:
+    cbc (PKCS#7 padding, default), cbc-cs1, cbc-cs2, cbc-cs3 (ciphertext stealing), ecb (no IV)
+passphrase options:
+    --passphrase p [--salt hex] [--kdf pbkdf2|evp] [--iterations n] [--md sha256|md5] [--hkdf-info label]";
 
 
 /**
  * Bitwise xor of two binary strings (represented as byte vectors)
  */
-fn xor_bytes(bin_1: &Vec<u8>, bin_2: &Vec<u8>) -> Vec<u8> {
-    let bin_xored: Vec<u8> = bin_1.iter()
+fn xor_bytes(bin_1: &[u8], bin_2: &[u8]) -> Vec<u8> {
+    bin_1.iter()
         .zip(bin_2)
         .map(|(x1, x2)| x1 ^ x2)
-        .collect();
-    
-    return bin_xored;
+        .collect()
+}
+
+/**
+ * Appends PKCS#7 padding (1 to 16 bytes) to the message
+ */
+fn pad(message: &[u8]) -> Vec<u8> {
+    let padding = 16 - message.len() % 16;
+    let mut padded = message.to_vec();
+    padded.extend(vec![padding as u8; padding]);
+    padded
+}
+
+/**
+ * Removes PKCS#7 padding, returns None if the padding is invalid
+ */
+fn unpad(plaintext: &[u8]) -> Option<&[u8]> {
+    let padding = *plaintext.last()? as usize;
+    if padding == 0 || padding > 16 || padding > plaintext.len() {
+        return None;
+    }
+
+    let (message, padding_bytes) = plaintext.split_at(plaintext.len() - padding);
+    match padding_bytes.iter().all(|x| *x as usize == padding) {
+        true => Some(message),
+        false => None,
+    }
 }
 
 /**
- * Returns String based on provided binary string representing ascii text
+ * Removes PKCS#7 padding if the plaintext ends with a valid one
  */
-fn bytes_to_ascii(bytes: Vec<u8>) -> String {
-    let plaintext_chars: Vec<char> = bytes.iter().map(|x| *x as char).collect();
-    let plaintext: String = plaintext_chars.iter().cloned().collect::<String>();
-    return plaintext;
+fn strip_padding(plaintext: &[u8]) -> &[u8] {
+    unpad(plaintext).unwrap_or(plaintext)
 }
 
 /**
- * Decrypts CBC cyphertext
+ * Encrypts the (padded) message in CBC mode, returns the IV-prefixed ciphertext
  */
-fn decrypt(key_hex: String, ciphertext_hex: String) -> String {
+fn encrypt(key: &[u8], iv: &[u8], message: &[u8]) -> Vec<u8> {
+
+    // initialize cipher
+    let cipher = cipher::Cipher::new(key);
+
+    // each block is xored with the previous ciphertext block (the IV for the first one)
+    let mut ciphertext: Vec<u8> = iv.to_vec();
+    for block in pad(message).chunks(16) {
+        let previous_block = &ciphertext[ciphertext.len() - 16..];
+        let mut ciphertext_block = xor_bytes(block, previous_block);
 
-    // convert all to byte arrays
-    let key: Vec<u8> = hex::decode(key_hex).unwrap();
-    let ciphertext: Vec<u8> = hex::decode(ciphertext_hex).unwrap();
+        cipher.encrypt_block(&mut ciphertext_block);
+        ciphertext.extend(ciphertext_block);
+    }
+
+    ciphertext
+}
+
+/**
+ * Decrypts CBC cyphertext (prefixed with the IV)
+ */
+fn decrypt(key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+    if ciphertext.len() < 16 || !ciphertext.len().is_multiple_of(16) {
+        return Err(format!("ciphertext must be the IV followed by whole 16 byte blocks, got {} bytes", ciphertext.len()));
+    }
 
     // initialize cipher
-    let cipher = Aes128::new(&GenericArray::from_slice(&key));
+    let cipher = cipher::Cipher::new(key);
 
     // run the decryption algorithm
     let n_blocks: i32 = ((ciphertext.len() as i32) / 16) - 1;
 
-    // convert message to ascii and return it
+    // decrypt block by block
     let mut message: Vec<u8> = Vec::new();
     for i in 0..n_blocks {
 
         // get nonce
         let nonce_start = 16 * (i) as usize;
-        let nonce_end =  nonce_start + 16 as usize;
+        let nonce_end =  nonce_start + 16;
         let nonce = ciphertext[nonce_start..nonce_end].to_vec();
 
         // get ciphertext block
         let block_start = 16 * (i + 1) as usize;
-        let block_end =  block_start + 16 as usize;
-        let mut ciphertext_block = GenericArray::clone_from_slice(&ciphertext[block_start..block_end]);
+        let block_end =  block_start + 16;
+        let mut ciphertext_block = ciphertext[block_start..block_end].to_vec();
 
         // decrypt block and add it to message
-        cipher.decrypt_block(& mut ciphertext_block);
-        let decrypted_block = xor_bytes(&ciphertext_block.to_vec() , &nonce);
+        cipher.decrypt_block(&mut ciphertext_block);
+        let decrypted_block = xor_bytes(&ciphertext_block, &nonce);
         message.extend(decrypted_block);
     }
 
     // return plaintext decrypted message
-    return bytes_to_ascii(message);
+    Ok(message)
+}
+
+/**
+ * Reads the input (structured file or legacy pair of files), exits with status 1 if it is invalid
+ */
+fn read_input_or_exit(
+    paths: &[String],
+    key_derivation: Option<&kdf::KeyDerivation>,
+    mode: input::Mode
+) -> Vec<input::Message> {
+    input::read_input(paths, key_derivation, mode).unwrap_or_else(|error| {
+        eprintln!("Invalid input: {}", error);
+        process::exit(1);
+    })
+}
+
+/**
+ * Decrypts every message of the input and prints the plaintexts
+ * Exits with status 1 if any plaintext differs from the expected one
+ */
+fn run_decrypt(args: &[String]) {
+    let (output_mode, key_derivation, mode, paths) = output::parse_output_args(args)
+        .and_then(|(output_mode, args)| {
+            let (key_derivation, args) = kdf::KeyDerivation::from_args(&args)?;
+            let (mode, paths) = input::Mode::from_args(&args)?;
+            Ok((output_mode, key_derivation, mode, paths))
+        })
+        .unwrap_or_else(|error| {
+            eprintln!("Invalid arguments: {}", error);
+            process::exit(2);
+        });
+    let messages = read_input_or_exit(&paths, key_derivation.as_ref(), mode);
+
+    // for each message, decrypt it and output the plaintext
+    let mut mismatches = 0;
+    for (index, message) in messages.iter().enumerate() {
+
+        let plaintext: Vec<u8> = match message.mode {
+            input::Mode::Cbc => decrypt(&message.key, &message.ciphertext),
+            input::Mode::Ecb => Ok(ecb::decrypt(&message.key, &message.ciphertext)),
+            input::Mode::Cts(variant) => cts::decrypt(&message.key, &message.ciphertext, variant),
+        }.unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+
+        println!("\nEncrypted message number {}:", index);
+        output::write_plaintext(&plaintext, index, &output_mode).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+
+        if let Some(expected) = &message.expected_plaintext {
+            // ciphertext stealing needs no padding
+            let message_bytes = match message.mode {
+                input::Mode::Cbc | input::Mode::Ecb => strip_padding(&plaintext),
+                input::Mode::Cts(_) => &plaintext,
+            };
+            if message_bytes != expected.as_bytes() {
+                println!("Plaintext does not match the expected plaintext");
+                mismatches += 1;
+            }
+        }
+    }
+
+    if mismatches > 0 {
+        process::exit(1);
+    }
+}
+
+/**
+ * Encrypts a file with the given key and IV (none in ECB mode), prints the IV-prefixed ciphertext in hex
+ */
+fn run_encrypt(args: &[String]) {
+    let (mode, args) = input::Mode::from_args(args).unwrap_or_else(|error| {
+        eprintln!("Invalid arguments: {}", error);
+        process::exit(2);
+    });
+    let (key, iv, path) = match (args.as_slice(), mode) {
+        ([key, path], input::Mode::Ecb) => (hex::decode(key), Ok(vec![0u8; 16]), path),
+        ([key, iv, path], _) if mode != input::Mode::Ecb => (hex::decode(key), hex::decode(iv), path),
+        _ => {
+            eprintln!("Expected a key, an IV (except in ECB mode) and an input file\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let (key, iv) = match (key, iv) {
+        (Ok(key), Ok(iv)) if key.len() == 16 && iv.len() == 16 => (key, iv),
+        _ => {
+            eprintln!("Key and IV must be 16 bytes in hex");
+            process::exit(2);
+        }
+    };
+
+    let result = fs::read(path)
+        .map_err(|x| format!("unable to read {}: {}", path, x))
+        .and_then(|message| match mode {
+            input::Mode::Cbc => Ok(encrypt(&key, &iv, &message)),
+            input::Mode::Cts(variant) => cts::encrypt(&key, &iv, &message, variant),
+            input::Mode::Ecb => Ok(ecb::encrypt(&key, &message)),
+        });
+
+    match result {
+        Ok(ciphertext) => println!("{}", hex::encode(ciphertext)),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/**
+ * Rewrites known plaintext of one block into the target by flipping bits of the previous ciphertext block
+ * With --key, decrypts the result and exits with status 1 unless the target appears and only the reported block changed
+ */
+fn run_bitflip(args: &[String]) {
+    let mut offset = 0;
+    let mut key: Option<String> = None;
+    let mut positional: Vec<String> = Vec::new();
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--offset" => offset = args_iter.next().and_then(|x| x.parse().ok()).unwrap_or_else(|| {
+                eprintln!("--offset requires a number");
+                process::exit(2);
+            }),
+            "--key" => key = args_iter.next().cloned(),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let (ciphertext, block, known, target) = match positional.as_slice() {
+        [ciphertext, block, known, target] => match (hex::decode(ciphertext), block.parse::<usize>()) {
+            (Ok(ciphertext), Ok(block)) if ciphertext.len() >= 32 && ciphertext.len().is_multiple_of(16) =>
+                (ciphertext, block, known.as_bytes(), target.as_bytes()),
+            _ => {
+                eprintln!("Expected an IV-prefixed ciphertext of whole blocks in hex and a block index");
+                process::exit(2);
+            }
+        },
+        _ => {
+            eprintln!("Expected a ciphertext, a block index, the known text and the target text\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let flipped = bitflip::flip(&ciphertext, block, offset, known, target).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    println!("{}", hex::encode(&flipped.ciphertext));
+    match flipped.scrambled_block {
+        Some(scrambled) => println!("Plaintext block {} is scrambled", scrambled),
+        None => println!("Only the IV was modified, no plaintext block is scrambled"),
+    }
+
+    // end-to-end check against the CBC decryption
+    if let Some(key) = key {
+        let key = hex::decode(&key).ok().filter(|x| x.len() == 16).unwrap_or_else(|| {
+            eprintln!("Key must be 16 bytes in hex");
+            process::exit(2);
+        });
+        let (original, modified) = match (decrypt(&key, &ciphertext), decrypt(&key, &flipped.ciphertext)) {
+            (Ok(original), Ok(modified)) => (original, modified),
+            (Err(error), _) | (_, Err(error)) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        };
+        println!("Modified plaintext: {}", modified.escape_ascii());
+
+        let start = 16 * block + offset;
+        let target_found = modified[start..start + target.len()] == *target;
+        let changed_blocks: Vec<usize> = original.chunks(16)
+            .zip(modified.chunks(16))
+            .enumerate()
+            .filter(|(index, (x, y))| x != y && *index != block)
+            .map(|(index, _)| index)
+            .collect();
+        let expected_changes: Vec<usize> = flipped.scrambled_block.into_iter().collect();
+
+        if target_found && changed_blocks == expected_changes {
+            println!("Verified: block {} contains the target, other changed blocks {:?}", block, changed_blocks);
+        } else {
+            println!("Verification failed: target found {}, other changed blocks {:?}", target_found, changed_blocks);
+            process::exit(1);
+        }
+    }
+}
+
+/**
+ * Random bytes from the OS
+ */
+fn random_bytes(n_bytes: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; n_bytes];
+    getrandom::getrandom(&mut bytes).expect("unable to read random bytes");
+    bytes
+}
+
+/**
+ * Lets the detector guess the mode of a random oracle
+ */
+fn run_ecb_detect(args: &[String]) {
+    if !args.is_empty() {
+        eprintln!("Expected no arguments\n{}", USAGE);
+        process::exit(2);
+    }
+
+    let oracle = ecb_attack::DetectionOracle::new();
+    println!("Detected {:?}, the oracle used {:?}", ecb_attack::detect_mode(&oracle), oracle.mode());
+}
+
+/**
+ * Recovers the secret suffix of an ECB oracle byte by byte, with --prefix the oracle also prepends 0-32 random bytes
+ * With --trials n, attacks n random secrets; exits with status 1 unless every secret is recovered exactly
+ */
+fn run_ecb_attack(args: &[String]) {
+    let mut random_prefix = false;
+    let mut n_trials: Option<usize> = None;
+    let mut paths: Vec<String> = Vec::new();
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--prefix" => random_prefix = true,
+            "--trials" => n_trials = Some(args_iter.next().and_then(|x| x.parse().ok()).unwrap_or_else(|| {
+                eprintln!("--trials requires a number");
+                process::exit(2);
+            })),
+            _ => paths.push(arg.clone()),
+        }
+    }
+
+    let secrets: Vec<Vec<u8>> = match (n_trials, paths.as_slice()) {
+        (Some(n_trials), []) => (0..n_trials).map(|_| random_bytes(random_bytes(1)[0] as usize)).collect(),
+        (None, [path]) => vec![fs::read(path).unwrap_or_else(|error| {
+            eprintln!("Unable to read {}: {}", path, error);
+            process::exit(1);
+        })],
+        (None, []) => vec![b"Rollin' in my 5.0\nWith my rag-top down so my hair can blow".to_vec()],
+        _ => {
+            eprintln!("Expected either a secret file or --trials n\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let mut n_recovered = 0;
+    for (trial, secret) in secrets.iter().enumerate() {
+        let prefix = match random_prefix {
+            true => random_bytes(random_bytes(1)[0] as usize % 33),
+            false => Vec::new(),
+        };
+        let mut oracle = ecb_attack::SuffixOracle::new(&prefix, secret);
+
+        match ecb_attack::recover_secret(&mut oracle) {
+            Ok(recovered) if recovered == *secret => {
+                n_recovered += 1;
+                if n_trials.is_none() {
+                    println!("Recovered secret: {}", recovered.escape_ascii());
+                    println!("Oracle queries: {}", oracle.n_queries());
+                }
+            }
+            Ok(recovered) => println!("trial {}: recovered {} instead of {}",
+                trial, recovered.escape_ascii(), secret.escape_ascii()),
+            Err(error) => println!("trial {}: {}", trial, error),
+        }
+    }
+    if n_trials.is_some() {
+        println!("{} of {} secrets recovered", n_recovered, secrets.len());
+    }
+
+    if n_recovered != secrets.len() {
+        process::exit(1);
+    }
 }
 
 /**
- * Reads a file and returns vector of lines from the file
+ * Derives the encrypt-then-MAC keys from a hex master key, exits with status 2 if it is invalid
  */
-fn read_lines(path: &str) -> Vec<String> {
-    let content_raw = fs::read_to_string(path)
-        .expect(format!("Please provide {} file with lines of hex strings in the project root", path).as_str());
+fn etm_keys_or_exit(master_key_hex: &str) -> etm::EtmKeys {
+    hex::decode(master_key_hex)
+        .map_err(|x| format!("invalid master key: {}", x))
+        .and_then(|master_key| etm::EtmKeys::derive(&master_key))
+        .unwrap_or_else(|error| {
+            eprintln!("Invalid arguments: {}", error);
+            process::exit(2);
+        })
+}
 
-    return content_raw.split('\n').map(|x| x.to_string()).collect();
+/**
+ * Encrypts a file into the encrypt-then-MAC container IV || ciphertext || HMAC-SHA256 tag
+ */
+fn run_etm_encrypt(args: &[String]) {
+    let (master_key, in_path, out_path) = match args {
+        [master_key, in_path, out_path] => (master_key, in_path, out_path),
+        _ => {
+            eprintln!("Expected a master key, an input file and an output file\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let keys = etm_keys_or_exit(master_key);
+
+    let result = fs::read(in_path)
+        .map_err(|x| format!("unable to read {}: {}", in_path, x))
+        .and_then(|message| fs::write(out_path, keys.seal(&random_bytes(16), &message))
+            .map_err(|x| format!("unable to write {}: {}", out_path, x)));
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+/**
+ * Verifies and decrypts an encrypt-then-MAC container, exits with status 1 if it is rejected
+ */
+fn run_etm_decrypt(args: &[String]) {
+    let (output_mode, args) = output::parse_output_args(args).unwrap_or_else(|error| {
+        eprintln!("Invalid arguments: {}", error);
+        process::exit(2);
+    });
+    let (master_key, path) = match args.as_slice() {
+        [master_key, path] => (master_key, path),
+        _ => {
+            eprintln!("Expected a master key and an input file\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let keys = etm_keys_or_exit(master_key);
+
+    let result = fs::read(path)
+        .map_err(|x| format!("unable to read {}: {}", path, x))
+        .and_then(|container| keys.open(&container).map_err(|x| format!("Rejected: {}", x)))
+        .and_then(|plaintext| output::write_plaintext(&plaintext, 0, &output_mode));
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+/**
+ * Recovers the key of a system using it as the IV (a random key if none is given)
+ * Exits with status 1 if the key is not recovered
+ */
+fn run_iv_key_attack(args: &[String]) {
+    let key = match args {
+        [] => random_bytes(16),
+        [key] => hex::decode(key).ok().filter(|x| x.len() == 16).unwrap_or_else(|| {
+            eprintln!("Invalid arguments: key must be 16 bytes in hex");
+            process::exit(2);
+        }),
+        _ => {
+            eprintln!("Invalid arguments: expected at most a key");
+            process::exit(2);
+        }
+    };
+    println!("Secret key: {}", hex::encode(&key));
+
+    let mut oracle = iv_key::Oracle::new(&key);
+    let ciphertext = oracle.encrypt(iv_key::EXAMPLE_MESSAGE);
+    match iv_key::recover_key(&mut oracle, &ciphertext) {
+        Ok(recovered) if recovered == key => {
+            println!("Recovered key: {}", hex::encode(&recovered));
+            println!("Decryption queries: {}", oracle.n_queries());
+        }
+        Ok(recovered) => {
+            println!("Wrong key recovered: {}", hex::encode(&recovered));
+            process::exit(1);
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/**
+ * Computes the CMAC (default) or raw CBC-MAC tag of a file and prints it in hex
+ * With --verify, exits with status 1 if the tag differs from the given one
+ */
+fn run_mac(args: &[String]) {
+    let mut algorithm = mac::MacAlgorithm::Cmac;
+    let mut expected_tag: Option<String> = None;
+    let mut paths: Vec<String> = Vec::new();
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--cmac" => algorithm = mac::MacAlgorithm::Cmac,
+            "--cbc-mac" => algorithm = mac::MacAlgorithm::CbcMac,
+            "--verify" => expected_tag = args_iter.next().cloned(),
+            _ => paths.push(arg.clone()),
+        }
+    }
+
+    let (key, path) = match paths.as_slice() {
+        [key, path] => match hex::decode(key) {
+            Ok(key) if key.len() == 16 => (key, path),
+            _ => {
+                eprintln!("Key must be 16 bytes in hex");
+                process::exit(2);
+            }
+        },
+        _ => {
+            eprintln!("Expected a key and an input file\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let tag = fs::read(path)
+        .map_err(|x| format!("unable to read {}: {}", path, x))
+        .and_then(|message| mac::tag(algorithm, &key, &message))
+        .unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+    println!("{}", hex::encode(&tag));
+
+    if let Some(expected_tag) = expected_tag {
+        let expected_tag = hex::decode(&expected_tag).unwrap_or_default();
+        if mac::constant_time_eq(&tag, &expected_tag) {
+            println!("Tag verified");
+        } else {
+            println!("Tag does not match");
+            process::exit(1);
+        }
+    }
+}
+
+/**
+ * Demonstrates the length-extension forgery against raw CBC-MAC and CMAC
+ * Exits with status 1 unless the forged CBC-MAC tag verifies and the forged CMAC tag does not
+ */
+fn run_mac_forgery(args: &[String]) {
+    let key = match args.first() {
+        Some(key) => hex::decode(key).ok().filter(|x| x.len() == 16).unwrap_or_else(|| {
+            eprintln!("Key must be 16 bytes in hex");
+            process::exit(2);
+        }),
+        None => random_bytes(16),
+    };
+
+    // two messages the oracle is willing to authenticate
+    let message_1 = b"To: bank        Pay Alice $100  ";
+    let message_2 = b"Pay Mallory $1000000, approved. ";
+
+    let mut failed = false;
+    for (name, algorithm, should_verify) in [
+        ("CBC-MAC", mac::MacAlgorithm::CbcMac, true),
+        ("CMAC", mac::MacAlgorithm::Cmac, false),
+    ] {
+        let forgery = forgery::forge(algorithm, &key, message_1, message_2).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+
+        println!("\n{}:", name);
+        println!("Forged message: {}", forgery.message.escape_ascii());
+        println!("Forged tag: {}", hex::encode(&forgery.tag));
+        println!("Forged tag {}", match forgery.verified {
+            true => "verifies",
+            false => "is rejected",
+        });
+        failed |= forgery.verified != should_verify;
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+/**
+ * Checks the input for reused and predictable IVs
+ * Exits with status 1 if any violation is found
+ */
+fn run_audit(args: &[String]) {
+    let (mode, paths) = input::Mode::from_args(args).unwrap_or_else(|error| {
+        eprintln!("Invalid arguments: {}", error);
+        process::exit(2);
+    });
+    let messages = read_input_or_exit(&paths, None, mode);
+
+    let findings = audit::audit_ivs(&messages);
+    for finding in &findings {
+        println!("{}", finding);
+    }
+
+    if findings.is_empty() {
+        println!("No IV violations found");
+    } else {
+        println!("{} IV violation(s) found", findings.len());
+        process::exit(1);
+    }
+}
+
+/**
+ * Parses arguments of the openssl-* commands, exits with status 2 if they are invalid
+ */
+fn parse_openssl_args_or_exit(args: &[String]) -> (openssl::OpensslOptions, Vec<String>) {
+    openssl::parse_openssl_args(args, &[EncCipher::Aes128Cbc]).unwrap_or_else(|error| {
+        eprintln!("Invalid arguments: {}", error);
+        process::exit(2);
+    })
+}
+
+/**
+ * Decrypts the contents of a file produced by `openssl enc -aes-128-cbc`
+ */
+fn decrypt_openssl(data: &[u8], options: &openssl::OpensslOptions) -> Result<Vec<u8>, String> {
+    let container = openssl::parse_container(data, options.base64)?;
+    if container.ciphertext.is_empty() || !container.ciphertext.len().is_multiple_of(16) {
+        return Err("ciphertext must be whole 16 byte blocks".to_string());
+    }
+    let (key, mut iv_ciphertext) = options.key_iv(container.salt.as_deref())?;
+    iv_ciphertext.extend(container.ciphertext);
+
+    let plaintext = decrypt(&key, &iv_ciphertext)?;
+    unpad(&plaintext).map(|x| x.to_vec()).ok_or_else(|| "bad decrypt (invalid padding)".to_string())
+}
+
+/**
+ * Decrypts a file produced by `openssl enc -aes-128-cbc` and outputs the plaintext
+ */
+fn run_openssl_decrypt(args: &[String]) {
+    let (output_mode, args) = output::parse_output_args(args).unwrap_or_else(|error| {
+        eprintln!("Invalid arguments: {}", error);
+        process::exit(2);
+    });
+    let (options, paths) = parse_openssl_args_or_exit(&args);
+    if paths.len() != 1 {
+        eprintln!("Expected exactly one input file");
+        process::exit(2);
+    }
+
+    let result = fs::read(&paths[0])
+        .map_err(|x| format!("unable to read {}: {}", paths[0], x))
+        .and_then(|data| decrypt_openssl(&data, &options))
+        .and_then(|plaintext| output::write_plaintext(&plaintext, 0, &output_mode));
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+/**
+ * Encrypts a message into the `openssl enc -aes-128-cbc` format
+ */
+fn encrypt_openssl(message: &[u8], options: &openssl::OpensslOptions) -> Result<Vec<u8>, String> {
+    let salt = options.new_salt()?;
+    let (key, iv) = options.key_iv(salt.as_deref())?;
+
+    // openssl does not store the IV, it is derived (or given) on decryption
+    let ciphertext = encrypt(&key, &iv, message)[16..].to_vec();
+    Ok(openssl::serialize_container(&openssl::Container { salt, ciphertext }, options.base64))
+}
+
+/**
+ * Encrypts a file into the `openssl enc -aes-128-cbc` format
+ */
+fn run_openssl_encrypt(args: &[String]) {
+    let (options, paths) = parse_openssl_args_or_exit(args);
+    if paths.len() != 2 {
+        eprintln!("Expected an input file and an output file");
+        process::exit(2);
+    }
+
+    let result = fs::read(&paths[0])
+        .map_err(|x| format!("unable to read {}: {}", paths[0], x))
+        .and_then(|message| encrypt_openssl(&message, &options))
+        .and_then(|data| fs::write(&paths[1], data).map_err(|x| format!("unable to write {}: {}", paths[1], x)));
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
 }
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
 
-    // load ciphertexts and keys
-    let ciphertexts: Vec<String> = read_lines("./input_ciphertexts.txt");
-    let keys: Vec<String> = read_lines("./input_keys.txt");
+    match args.get(1).map(|x| x.as_str()) {
+        None => run_decrypt(&[]),
+        Some("decrypt") => run_decrypt(&args[2..]),
+        Some("encrypt") => run_encrypt(&args[2..]),
+        Some("bitflip") => run_bitflip(&args[2..]),
+        Some("ecb-detect") => run_ecb_detect(&args[2..]),
+        Some("ecb-attack") => run_ecb_attack(&args[2..]),
+        Some("etm-encrypt") => run_etm_encrypt(&args[2..]),
+        Some("etm-decrypt") => run_etm_decrypt(&args[2..]),
+        Some("iv-key-attack") => run_iv_key_attack(&args[2..]),
+        Some("mac") => run_mac(&args[2..]),
+        Some("mac-forgery") => run_mac_forgery(&args[2..]),
+        Some("audit") => run_audit(&args[2..]),
+        Some("openssl-decrypt") => run_openssl_decrypt(&args[2..]),
+        Some("openssl-encrypt") => run_openssl_encrypt(&args[2..]),
+        Some(command) => {
+            eprintln!("Unknown command {}\n{}", command, USAGE);
+            process::exit(2);
+        }
+    }
+}
 
-    // for each pair, dedcrypt the message and print the acsii plaintext
-    for (index, (ciphertext, key)) in ciphertexts.into_iter().zip(keys).enumerate() {
-        
-        let message: String = decrypt(key, ciphertext);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        println!("\nEncrypted message number {}:", index);
-        println!("{}", message);
-    } 
+    // files written by openssl enc from fixtures/plaintext.txt and the options that decrypt them
+    const FIXTURES: &[(&str, &[&str])] = &[
+        ("aes-128-cbc-pbkdf2.bin", &["--passphrase", "fixture"]),
+        ("aes-128-cbc-pbkdf2.b64", &["-a", "--passphrase", "fixture"]),
+        ("aes-128-cbc-md5.bin", &["--passphrase", "fixture", "--kdf", "evp", "--md", "md5"]),
+        ("aes-128-cbc-key-iv.bin", &["-K", "000102030405060708090a0b0c0d0e0f", "-iv", "f0e0d0c0b0a090807060504030201000"]),
+    ];
+
+    fn options(args: &[&str]) -> openssl::OpensslOptions {
+        let args: Vec<String> = args.iter().map(|x| x.to_string()).collect();
+        let (options, remaining) = openssl::parse_openssl_args(&args, &[EncCipher::Aes128Cbc]).unwrap();
+        assert!(remaining.is_empty());
+        options
+    }
+
+    #[test]
+    fn openssl_fixtures() {
+        let plaintext = fs::read("fixtures/plaintext.txt").unwrap();
+        for (name, args) in FIXTURES {
+            let data = fs::read(format!("fixtures/{}", name)).unwrap();
+            assert_eq!(decrypt_openssl(&data, &options(args)).unwrap(), plaintext, "{}", name);
+        }
+
+        // a salted file needs the passphrase, an unsalted one the key
+        let salted = fs::read("fixtures/aes-128-cbc-pbkdf2.bin").unwrap();
+        assert!(decrypt_openssl(&salted, &options(FIXTURES[3].1)).is_err());
+        let unsalted = fs::read("fixtures/aes-128-cbc-key-iv.bin").unwrap();
+        assert!(decrypt_openssl(&unsalted, &options(FIXTURES[0].1)).is_err());
+    }
+
+    #[test]
+    fn openssl_round_trip() {
+        let plaintext = fs::read("fixtures/plaintext.txt").unwrap();
+        for (name, args) in FIXTURES {
+            // a random salt every time
+            let data = encrypt_openssl(&plaintext, &options(args)).unwrap();
+            assert_eq!(decrypt_openssl(&data, &options(args)).unwrap(), plaintext, "{}", name);
+
+            // with the salt of the fixture, the file openssl wrote
+            let fixture = fs::read(format!("fixtures/{}", name)).unwrap();
+            let mut args: Vec<&str> = args.to_vec();
+            let salt = openssl::parse_container(&fixture, args.contains(&"-a")).unwrap().salt.map(hex::encode);
+            if let Some(salt) = &salt {
+                args.extend(["--salt", salt]);
+            }
+            assert_eq!(encrypt_openssl(&plaintext, &options(&args)).unwrap(), fixture, "{}", name);
+        }
+    }
+
+    #[test]
+    fn cbc_round_trip() {
+        let key = random_bytes(16);
+        for length in 0..=48 {
+            let message = random_bytes(length);
+            let ciphertext = encrypt(&key, &random_bytes(16), &message);
+            assert_eq!(unpad(&decrypt(&key, &ciphertext).unwrap()), Some(&message[..]));
+        }
+    }
+
+    #[test]
+    fn partial_blocks() {
+        for length in [0, 15, 17, 31, 33, 47] {
+            assert!(decrypt(&[0u8; 16], &vec![0u8; length]).is_err());
+        }
+        assert_eq!(decrypt(&[0u8; 16], &[0u8; 16]), Ok(Vec::new()));
+    }
 }
@@ -0,0 +1,140 @@
+use crate::input::Message;
+
+/**
+ * Range of counter blocks (nonce, nonce + 1, ...) consumed by a single message
+ */
+struct CounterRange {
+    index: usize,
+    key: Vec<u8>,
+    start: u128,
+    n_blocks: u128,
+}
+
+/**
+ * Interprets a 16 byte block as a big-endian 128-bit integer
+ */
+fn block_to_u128(block: &[u8]) -> u128 {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&block[0..16]);
+    u128::from_be_bytes(bytes)
+}
+
+/**
+ * Returns the number of counter blocks shared by the two ranges
+ * (the counter wraps around the same way as in increment_nonce)
+ */
+fn shared_blocks(range_1: &CounterRange, range_2: &CounterRange) -> u128 {
+    let offset_1 = range_2.start.wrapping_sub(range_1.start);
+    let offset_2 = range_1.start.wrapping_sub(range_2.start);
+
+    if offset_1 < range_1.n_blocks {
+        (range_1.n_blocks - offset_1).min(range_2.n_blocks)
+    } else if offset_2 < range_2.n_blocks {
+        (range_2.n_blocks - offset_2).min(range_1.n_blocks)
+    } else {
+        0
+    }
+}
+
+/**
+ * Checks nonces of CTR messages (nonce-prefixed ciphertexts)
+ * Returns a human readable finding for every reused nonce or overlapping counter range
+ */
+pub fn audit_nonces(messages: &[Message]) -> Vec<String> {
+    let mut findings: Vec<String> = Vec::new();
+    let mut ranges: Vec<CounterRange> = Vec::new();
+
+    for (index, message) in messages.iter().enumerate() {
+        let ciphertext = &message.ciphertext;
+        let range = CounterRange {
+            index,
+            key: message.key.clone(),
+            start: block_to_u128(&ciphertext[0..16]),
+            n_blocks: ((ciphertext.len() - 16) as u128).div_ceil(16),
+        };
+
+        // any counter block encrypted twice under the same key reuses keystream
+        for previous in ranges.iter().filter(|x| x.key == range.key) {
+            if previous.start == range.start {
+                findings.push(format!("message {}: reuses the nonce of message {}",
+                    range.index, previous.index));
+                continue;
+            }
+
+            let shared = shared_blocks(previous, &range);
+            if shared > 0 {
+                findings.push(format!("message {}: {} counter block(s) overlap with message {}",
+                    range.index, shared, previous.index));
+            }
+        }
+        ranges.push(range);
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 16] = [1; 16];
+
+    fn message(key: &[u8], nonce: u128, length: usize) -> Message {
+        let mut ciphertext = nonce.to_be_bytes().to_vec();
+        ciphertext.extend(vec![0u8; length]);
+        Message { key: key.to_vec(), ciphertext, expected_plaintext: None }
+    }
+
+    fn range(start: u128, n_blocks: u128) -> CounterRange {
+        CounterRange { index: 0, key: Vec::new(), start, n_blocks }
+    }
+
+    #[test]
+    fn shared() {
+        assert_eq!(shared_blocks(&range(10, 5), &range(12, 5)), 3);
+        assert_eq!(shared_blocks(&range(12, 5), &range(10, 5)), 3);
+        assert_eq!(shared_blocks(&range(10, 10), &range(12, 2)), 2);
+        assert_eq!(shared_blocks(&range(10, 5), &range(15, 5)), 0);
+        assert_eq!(shared_blocks(&range(10, 0), &range(10, 5)), 0);
+
+        // ranges wrapping around the 128-bit counter
+        assert_eq!(shared_blocks(&range(u128::MAX - 1, 4), &range(0, 10)), 2);
+        assert_eq!(shared_blocks(&range(1, 3), &range(u128::MAX, 3)), 1);
+        assert_eq!(shared_blocks(&range(u128::MAX, 1), &range(0, 1)), 0);
+    }
+
+    #[test]
+    fn clean() {
+        // adjacent counter ranges and distant nonces
+        let mut messages = vec![message(&KEY, 100, 64), message(&KEY, 104, 20), message(&KEY, 106, 1)];
+        messages.extend((1..20).map(|i| message(&KEY, i << 64, 100)));
+        assert!(audit_nonces(&messages).is_empty());
+
+        // the same nonce under different keys
+        assert!(audit_nonces(&[message(&KEY, 7, 32), message(&[2; 16], 7, 32)]).is_empty());
+    }
+
+    #[test]
+    fn reused_nonce() {
+        let messages = [message(&KEY, 7, 32), message(&KEY, 1000, 32), message(&KEY, 7, 5)];
+        assert_eq!(audit_nonces(&messages), ["message 2: reuses the nonce of message 0"]);
+    }
+
+    #[test]
+    fn overlapping_counters() {
+        // blocks 100 to 103, then 102 to 104, then 103
+        let messages = [message(&KEY, 100, 64), message(&KEY, 102, 40), message(&KEY, 103, 1)];
+        assert_eq!(audit_nonces(&messages), [
+            "message 1: 2 counter block(s) overlap with message 0",
+            "message 2: 1 counter block(s) overlap with message 0",
+            "message 2: 1 counter block(s) overlap with message 1",
+        ]);
+    }
+
+    #[test]
+    fn wraparound() {
+        // blocks MAX - 1, MAX, 0 and 1, then 1 and 2
+        let messages = [message(&KEY, u128::MAX - 1, 64), message(&KEY, 1, 17)];
+        assert_eq!(audit_nonces(&messages), ["message 1: 1 counter block(s) overlap with message 0"]);
+    }
+}
@@ -0,0 +1,83 @@
+#[cfg(not(feature = "scratch-aes"))]
+use aes::{Aes128, Aes192, Aes256};
+#[cfg(not(feature = "scratch-aes"))]
+use aes::cipher::{
+    BlockEncrypt, NewBlockCipher,
+    generic_array::GenericArray
+};
+#[cfg(feature = "scratch-aes")]
+use std::convert::TryInto;
+#[cfg(feature = "scratch-aes")]
+use aes_core::BlockCipher;
+
+// the in-repo AES: table based by default, bitsliced with the scratch-aes-ct feature
+#[cfg(all(feature = "scratch-aes", not(feature = "scratch-aes-ct")))]
+type ScratchAes = aes_core::AesTables;
+#[cfg(feature = "scratch-aes-ct")]
+type ScratchAes = aes_core::AesBitsliced;
+
+/**
+ * AES block cipher used by CTR mode and the DRBG, the key length (16, 24 or 32 bytes) selects the variant
+ * Backed by the aes crate, or by the in-repo implementation with the scratch-aes feature
+ */
+pub struct Cipher {
+    #[cfg(not(feature = "scratch-aes"))]
+    inner: AesCrate,
+    #[cfg(feature = "scratch-aes")]
+    inner: ScratchAes,
+}
+
+#[cfg(not(feature = "scratch-aes"))]
+enum AesCrate {
+    Aes128(Aes128),
+    Aes192(Aes192),
+    Aes256(Aes256),
+}
+
+#[cfg(not(feature = "scratch-aes"))]
+impl Cipher {
+
+    /**
+     * Expands the key, panics if its length is invalid (keys are validated when reading the input)
+     */
+    pub fn new(key: &[u8]) -> Cipher {
+        let inner = match key.len() {
+            16 => AesCrate::Aes128(Aes128::new(GenericArray::from_slice(key))),
+            24 => AesCrate::Aes192(Aes192::new(GenericArray::from_slice(key))),
+            32 => AesCrate::Aes256(Aes256::new(GenericArray::from_slice(key))),
+            other => panic!("AES key must be 16, 24 or 32 bytes long, got {}", other),
+        };
+        Cipher { inner }
+    }
+
+    /**
+     * Encrypts a 16 byte block in place
+     */
+    pub fn encrypt_block(&self, block: &mut [u8]) {
+        let block = GenericArray::from_mut_slice(block);
+        match &self.inner {
+            AesCrate::Aes128(cipher) => cipher.encrypt_block(block),
+            AesCrate::Aes192(cipher) => cipher.encrypt_block(block),
+            AesCrate::Aes256(cipher) => cipher.encrypt_block(block),
+        }
+    }
+}
+
+#[cfg(feature = "scratch-aes")]
+impl Cipher {
+
+    /**
+     * Expands the key, panics if its length is invalid (keys are validated when reading the input)
+     */
+    pub fn new(key: &[u8]) -> Cipher {
+        Cipher { inner: ScratchAes::new(key).unwrap_or_else(|error| panic!("{}", error)) }
+    }
+
+    /**
+     * Encrypts a 16 byte block in place
+     */
+    pub fn encrypt_block(&self, block: &mut [u8]) {
+        let block: &mut aes_core::Block = block.try_into().expect("AES block must be 16 bytes long");
+        self.inner.encrypt_block(block);
+    }
+}
@@ -0,0 +1,424 @@
+use std::error::Error;
+use std::fmt;
+use rand_core::{CryptoRng, RngCore};
+use crate::cipher::Cipher;
+
+// limits from NIST SP 800-90A, table 3
+pub const MAX_BYTES_PER_REQUEST: usize = 1 << 16;
+const MAX_INPUT_LENGTH: usize = 1 << 32;
+const RESEED_INTERVAL: u64 = 1 << 48;
+
+/**
+ * Source of fresh entropy used for (re)seeding the DRBG
+ */
+pub type EntropySource = Box<dyn FnMut(&mut [u8])>;
+
+/**
+ * Errors returned by the DRBG functions
+ */
+#[derive(Debug)]
+pub enum DrbgError {
+    InvalidInputLength(&'static str, usize),
+    RequestTooLarge(usize),
+}
+
+impl fmt::Display for DrbgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DrbgError::InvalidInputLength(name, length) =>
+                write!(f, "invalid {} length of {} bytes", name, length),
+            DrbgError::RequestTooLarge(length) =>
+                write!(f, "request of {} bytes exceeds {} bytes per generate call", length, MAX_BYTES_PER_REQUEST),
+        }
+    }
+}
+
+impl Error for DrbgError {}
+
+/**
+ * Block cipher of the DRBG (AES-128 or AES-256)
+ */
+#[derive(Clone, Copy)]
+pub enum DrbgCipher {
+    Aes128,
+    Aes256,
+}
+
+impl DrbgCipher {
+    fn key_length(&self) -> usize {
+        match self {
+            DrbgCipher::Aes128 => 16,
+            DrbgCipher::Aes256 => 32,
+        }
+    }
+}
+
+/**
+ * Encrypts a single block under the given key, the key length selects AES-128 or AES-256
+ */
+fn encrypt_block(key: &[u8], block: &[u8]) -> Vec<u8> {
+    let mut block_mutable = block.to_vec();
+    Cipher::new(key).encrypt_block(&mut block_mutable);
+    block_mutable
+}
+
+/**
+ * Block_Cipher_df from SP 800-90A, section 10.3.2
+ * Compresses input of arbitrary length into n_bytes of seed material
+ */
+fn block_cipher_df(key_length: usize, input: &[u8], n_bytes: usize) -> Vec<u8> {
+
+    // S = L || N || input || 0x80, padded with zeros to the block length
+    let mut s: Vec<u8> = Vec::new();
+    s.extend(&(input.len() as u32).to_be_bytes());
+    s.extend(&(n_bytes as u32).to_be_bytes());
+    s.extend(input);
+    s.push(0x80);
+    while !s.len().is_multiple_of(16) {
+        s.push(0);
+    }
+
+    // BCC with the fixed key 0x00 0x01 ... derives the new key and X
+    let key: Vec<u8> = (0..key_length as u8).collect();
+    let mut temp: Vec<u8> = Vec::new();
+    let mut i: u32 = 0;
+    while temp.len() < key_length + 16 {
+        let mut chaining_value = vec![0u8; 16];
+        chaining_value[0..4].copy_from_slice(&i.to_be_bytes());
+        chaining_value = encrypt_block(&key, &chaining_value);
+
+        for block in s.chunks(16) {
+            chaining_value = encrypt_block(&key, &crate::xor_bytes(&chaining_value, block));
+        }
+        temp.extend(chaining_value);
+        i += 1;
+    }
+
+    // encrypt X repeatedly to get the requested number of bytes
+    let key = temp[0..key_length].to_vec();
+    let mut x = temp[key_length..key_length + 16].to_vec();
+    let mut output: Vec<u8> = Vec::new();
+    while output.len() < n_bytes {
+        x = encrypt_block(&key, &x);
+        output.extend(&x);
+    }
+    output.truncate(n_bytes);
+    output
+}
+
+/**
+ * CTR_DRBG from NIST SP 800-90A (AES-128/AES-256, with or without derivation function)
+ */
+pub struct CtrDrbg {
+    key: Vec<u8>,
+    v: Vec<u8>,
+    reseed_counter: u64,
+    derivation_function: bool,
+    prediction_resistance: bool,
+    entropy_source: EntropySource,
+}
+
+impl CtrDrbg {
+
+    /**
+     * Instantiates the DRBG, pulling the initial entropy from the entropy source
+     * The nonce is only used with the derivation function
+     */
+    pub fn new(
+        cipher: DrbgCipher,
+        derivation_function: bool,
+        prediction_resistance: bool,
+        entropy_source: EntropySource,
+        nonce: &[u8],
+        personalization: &[u8]
+    ) -> Result<CtrDrbg, DrbgError> {
+
+        let key_length = cipher.key_length();
+        let mut drbg = CtrDrbg {
+            key: vec![0; key_length],
+            v: vec![0; 16],
+            reseed_counter: 1,
+            derivation_function,
+            prediction_resistance,
+            entropy_source,
+        };
+
+        let entropy = drbg.get_entropy();
+        let seed_material = if derivation_function {
+            if nonce.len() > MAX_INPUT_LENGTH || personalization.len() > MAX_INPUT_LENGTH {
+                return Err(DrbgError::InvalidInputLength("nonce or personalization", nonce.len() + personalization.len()));
+            }
+            let mut input = entropy;
+            input.extend(nonce);
+            input.extend(personalization);
+            block_cipher_df(key_length, &input, drbg.seed_length())
+        } else {
+            drbg.pad_to_seed_length(&entropy, personalization, "personalization")?
+        };
+
+        drbg.update(&seed_material);
+        Ok(drbg)
+    }
+
+    /**
+     * Key length plus block length
+     */
+    fn seed_length(&self) -> usize {
+        self.key.len() + 16
+    }
+
+    /**
+     * Reads fresh entropy (security strength with df, full seed length without it)
+     */
+    fn get_entropy(&mut self) -> Vec<u8> {
+        let length = match self.derivation_function {
+            true => self.key.len(),
+            false => self.seed_length(),
+        };
+        let mut entropy = vec![0u8; length];
+        (self.entropy_source)(&mut entropy);
+        entropy
+    }
+
+    /**
+     * Without derivation function the input is xored into the seed material
+     * (zero padded), so it can not be longer than the seed length
+     */
+    fn pad_to_seed_length(&self, material: &[u8], input: &[u8], name: &'static str) -> Result<Vec<u8>, DrbgError> {
+        if input.len() > self.seed_length() {
+            return Err(DrbgError::InvalidInputLength(name, input.len()));
+        }
+        let mut padded = input.to_vec();
+        padded.resize(self.seed_length(), 0);
+        Ok(crate::xor_bytes(material, &padded))
+    }
+
+    /**
+     * Processes the additional input the same way as seed material
+     */
+    fn prepare_additional_input(&self, additional_input: &[u8]) -> Result<Vec<u8>, DrbgError> {
+        if additional_input.is_empty() {
+            return Ok(vec![0; self.seed_length()]);
+        }
+        match self.derivation_function {
+            true if additional_input.len() > MAX_INPUT_LENGTH =>
+                Err(DrbgError::InvalidInputLength("additional input", additional_input.len())),
+            true => Ok(block_cipher_df(self.key.len(), additional_input, self.seed_length())),
+            false => self.pad_to_seed_length(&vec![0; self.seed_length()], additional_input, "additional input"),
+        }
+    }
+
+    /**
+     * CTR_DRBG_Update: runs the counter mode over V and replaces key and V
+     */
+    fn update(&mut self, provided_data: &[u8]) {
+        let mut temp: Vec<u8> = Vec::new();
+        while temp.len() < self.seed_length() {
+            self.v = crate::increment_nonce(&self.v);
+            temp.extend(encrypt_block(&self.key, &self.v));
+        }
+        temp.truncate(self.seed_length());
+
+        let temp = crate::xor_bytes(&temp, provided_data);
+        let key_length = self.key.len();
+        self.key = temp[0..key_length].to_vec();
+        self.v = temp[key_length..].to_vec();
+    }
+
+    /**
+     * Mixes fresh entropy from the entropy source and the additional input into the state
+     */
+    pub fn reseed(&mut self, additional_input: &[u8]) -> Result<(), DrbgError> {
+        let entropy = self.get_entropy();
+        let seed_material = if self.derivation_function {
+            if additional_input.len() > MAX_INPUT_LENGTH {
+                return Err(DrbgError::InvalidInputLength("additional input", additional_input.len()));
+            }
+            let mut input = entropy;
+            input.extend(additional_input);
+            block_cipher_df(self.key.len(), &input, self.seed_length())
+        } else {
+            self.pad_to_seed_length(&entropy, additional_input, "additional input")?
+        };
+
+        self.update(&seed_material);
+        self.reseed_counter = 1;
+        Ok(())
+    }
+
+    /**
+     * Fills the output with pseudorandom bytes
+     * Reseeds before the request if prediction resistance is on or the reseed interval is reached
+     */
+    pub fn generate(&mut self, output: &mut [u8], additional_input: &[u8]) -> Result<(), DrbgError> {
+        if output.len() > MAX_BYTES_PER_REQUEST {
+            return Err(DrbgError::RequestTooLarge(output.len()));
+        }
+
+        // the additional input is consumed by the reseed
+        let mut additional_input = additional_input;
+        if self.prediction_resistance || self.reseed_counter > RESEED_INTERVAL {
+            self.reseed(additional_input)?;
+            additional_input = &[];
+        }
+
+        let has_additional_input = !additional_input.is_empty();
+        let additional_input = self.prepare_additional_input(additional_input)?;
+        if has_additional_input {
+            self.update(&additional_input);
+        }
+
+        // counter mode keystream
+        for block in output.chunks_mut(16) {
+            self.v = crate::increment_nonce(&self.v);
+            let keystream = encrypt_block(&self.key, &self.v);
+            block.copy_from_slice(&keystream[0..block.len()]);
+        }
+
+        self.update(&additional_input);
+        self.reseed_counter += 1;
+        Ok(())
+    }
+}
+
+impl RngCore for CtrDrbg {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest).expect("CTR_DRBG generate failed")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        for chunk in dest.chunks_mut(MAX_BYTES_PER_REQUEST) {
+            self.generate(chunk, &[]).map_err(rand_core::Error::new)?;
+        }
+        Ok(())
+    }
+}
+
+impl CryptoRng for CtrDrbg {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+    use super::*;
+
+    /**
+     * Runs the tests of a CAVP style response file, whose fields are listed in the order of the calls:
+     * instantiate, generate for every AdditionalInput, reseed at AdditionalInputReseed, ReturnedBits is the last output
+     * With prediction resistance every generate reseeds first, so it runs once the EntropyInputPR is known
+     * Returns the number of tests
+     */
+    fn run_response_file(path: &str) -> usize {
+        let text = fs::read_to_string(path).unwrap();
+        let entropy: Rc<RefCell<Vec<u8>>> = Rc::new(RefCell::new(Vec::new()));
+
+        let (mut cipher, mut derivation_function, mut n_bytes) = (DrbgCipher::Aes128, true, 0);
+        let mut prediction_resistance = false;
+        let (mut count, mut nonce, mut personalization) = (String::new(), Vec::new(), Vec::new());
+        let mut additional_input: Vec<u8> = Vec::new();
+        let mut drbg: Option<CtrDrbg> = None;
+        let mut output: Vec<u8> = Vec::new();
+        let mut n_tests = 0;
+
+        for line in text.lines().filter(|x| !x.starts_with('#') && !x.trim().is_empty()) {
+            if let Some(section) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+                match section.split_once(" = ") {
+                    Some(("ReturnedBitsLen", bits)) => n_bytes = bits.parse::<usize>().unwrap() / 8,
+                    Some(("PredictionResistance", value)) => prediction_resistance = value == "True",
+                    Some(_) => {}
+                    None => {
+                        cipher = match section.starts_with("AES-128") {
+                            true => DrbgCipher::Aes128,
+                            false => DrbgCipher::Aes256,
+                        };
+                        derivation_function = section.ends_with("use df");
+                    }
+                }
+                continue;
+            }
+
+            let (name, value) = line.split_once(" =").unwrap();
+            let value = value.trim();
+            if name == "COUNT" {
+                count = value.to_string();
+                drbg = None;
+                nonce.clear();
+                personalization.clear();
+                continue;
+            }
+            let value = hex::decode(value).unwrap();
+
+            // the DRBG is instantiated before the first generate or reseed call
+            if drbg.is_none() && (name == "AdditionalInput" || name == "EntropyInputReseed") {
+                let source_entropy = Rc::clone(&entropy);
+                let entropy_source: EntropySource = Box::new(move |dest: &mut [u8]| dest.copy_from_slice(&source_entropy.borrow()));
+                drbg = Some(CtrDrbg::new(cipher, derivation_function, prediction_resistance, entropy_source, &nonce, &personalization).unwrap());
+            }
+            match name {
+                "EntropyInput" | "EntropyInputReseed" | "EntropyInputPR" => *entropy.borrow_mut() = value,
+                "Nonce" => nonce = value,
+                "PersonalizationString" => personalization = value,
+                "AdditionalInput" => additional_input = value,
+                "AdditionalInputReseed" => drbg.as_mut().unwrap().reseed(&value).unwrap(),
+                "ReturnedBits" => {
+                    assert_eq!(hex::encode(&output), hex::encode(&value), "{} COUNT = {}", path, count);
+                    n_tests += 1;
+                }
+                other => panic!("unknown field {} in {}", other, path),
+            }
+
+            let generate_after = match prediction_resistance {
+                true => "EntropyInputPR",
+                false => "AdditionalInput",
+            };
+            if name == generate_after {
+                output = vec![0u8; n_bytes];
+                drbg.as_mut().unwrap().generate(&mut output, &additional_input).unwrap();
+            }
+        }
+        n_tests
+    }
+
+    #[test]
+    fn cavp_aes128_df() {
+        assert_eq!(run_response_file("fixtures/ctr_drbg_aes128_df.rsp"), 9);
+    }
+
+    #[test]
+    fn cavp_aes256_df() {
+        assert_eq!(run_response_file("fixtures/ctr_drbg_aes256_df.rsp"), 61);
+    }
+
+    #[test]
+    fn cavp_aes128_df_prediction_resistance() {
+        assert_eq!(run_response_file("fixtures/ctr_drbg_aes128_df_pr.rsp"), 1);
+    }
+
+    #[test]
+    fn cavp_aes256_df_prediction_resistance() {
+        assert_eq!(run_response_file("fixtures/ctr_drbg_aes256_df_pr.rsp"), 61);
+    }
+
+    #[test]
+    fn known_answers_aes128_no_df() {
+        assert_eq!(run_response_file("fixtures/ctr_drbg_aes128_no_df.rsp"), 12);
+    }
+
+    #[test]
+    fn cavp_aes256_no_df() {
+        assert_eq!(run_response_file("fixtures/ctr_drbg_aes256_no_df.rsp"), 1);
+    }
+}
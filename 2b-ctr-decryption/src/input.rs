@@ -0,0 +1,115 @@
+use cipher_cli::input::{self, decode_field, Record};
+use cipher_cli::kdf::KeyDerivation;
+
+/**
+ * Validated message ready for decryption
+ */
+pub struct Message {
+    pub key: Vec<u8>,
+    // nonce-prefixed ciphertext
+    pub ciphertext: Vec<u8>,
+    pub expected_plaintext: Option<String>,
+}
+
+/**
+ * Checks lengths of a decoded message
+ */
+fn validate(message: &Message, index: usize) -> Result<(), String> {
+    if message.key.len() != 16 {
+        return Err(format!("record {}: key must be 16 bytes long, got {}", index, message.key.len()));
+    }
+    if message.ciphertext.len() < 16 {
+        return Err(format!("record {}: ciphertext must contain the 16 byte nonce, got {} bytes",
+            index, message.ciphertext.len()));
+    }
+    Ok(())
+}
+
+/**
+ * Validates a record and converts it into a message
+ */
+fn record_to_message(record: Record, index: usize, key_derivation: Option<&KeyDerivation>) -> Result<Message, String> {
+    if let Some(mode) = &record.mode {
+        if mode.to_lowercase() != "ctr" {
+            return Err(format!("record {}: mode {} is not supported, expected ctr", index, mode));
+        }
+    }
+
+    let mut ciphertext = match &record.iv {
+        Some(nonce) => {
+            let nonce = decode_field(nonce, record.encoding, "nonce", index)?;
+            if nonce.len() != 16 {
+                return Err(format!("record {}: nonce must be 16 bytes long, got {}", index, nonce.len()));
+            }
+            nonce
+        }
+        None => Vec::new(),
+    };
+    ciphertext.extend(decode_field(&record.ciphertext, record.encoding, "ciphertext", index)?);
+
+    // the passphrase takes precedence over the key of the record
+    let (key, ciphertext) = match (key_derivation, &record.key) {
+        (Some(key_derivation), _) => key_derivation.derive_message_key(&ciphertext)
+            .map_err(|x| format!("record {}: {}", index, x))?,
+        (None, Some(key)) => (decode_field(key, record.encoding, "key", index)?, ciphertext),
+        (None, None) => return Err(format!("record {}: key is missing and no passphrase was given", index)),
+    };
+
+    let message = Message { key, ciphertext, expected_plaintext: record.expected_plaintext };
+    validate(&message, index)?;
+    Ok(message)
+}
+
+/**
+ * Reads either the structured input (one path) or the legacy input (two paths), see `cipher_cli::input::read_input`
+ */
+pub fn read_input(paths: &[String], key_derivation: Option<&KeyDerivation>) -> Result<Vec<Message>, String> {
+    input::read_input(paths, key_derivation, record_to_message)
+}
+
+#[cfg(test)]
+mod tests {
+    use cipher_cli::input::Encoding;
+    use super::*;
+
+    fn record(key: &str, nonce: Option<&str>, ciphertext: &str, mode: Option<&str>) -> Record {
+        Record {
+            key: Some(key.to_string()),
+            iv: nonce.map(|x| x.to_string()),
+            ciphertext: ciphertext.to_string(),
+            encoding: Encoding::Hex,
+            mode: mode.map(|x| x.to_string()),
+            expected_plaintext: None,
+        }
+    }
+
+    fn error(record: Record) -> String {
+        record_to_message(record, 4, None).err().unwrap()
+    }
+
+    #[test]
+    fn valid_records() {
+        let key = "00".repeat(16);
+        let message = record_to_message(record(&key, Some(&"11".repeat(16)), &"22".repeat(5), Some("CTR")), 0, None).unwrap();
+        assert_eq!((message.key.len(), message.ciphertext.len()), (16, 21));
+        let message = record_to_message(record(&key, None, &"22".repeat(16), None), 0, None).unwrap();
+        assert_eq!(message.ciphertext.len(), 16);
+    }
+
+    #[test]
+    fn invalid_records() {
+        let key = "00".repeat(16);
+        let nonce = "11".repeat(16);
+        assert_eq!(error(record(&"00".repeat(32), Some(&nonce), "22", None)), "record 4: key must be 16 bytes long, got 32");
+        assert_eq!(error(record(&key, Some(&"11".repeat(12)), "22", None)), "record 4: nonce must be 16 bytes long, got 12");
+        assert_eq!(error(record(&key, None, &"22".repeat(15), None)),
+            "record 4: ciphertext must contain the 16 byte nonce, got 15 bytes");
+        assert_eq!(error(record(&key, Some(&nonce), "22", Some("cbc"))), "record 4: mode cbc is not supported, expected ctr");
+        assert!(error(record(&key, Some("11zz"), "22", None)).starts_with("record 4: nonce is not valid"));
+        assert!(error(record(&key, Some(&nonce), "2", None)).starts_with("record 4: ciphertext is not valid"));
+
+        let mut keyless = record(&key, Some(&nonce), "22", None);
+        keyless.key = None;
+        assert_eq!(error(keyless), "record 4: key is missing and no passphrase was given");
+    }
+}
@@ -1,39 +1,49 @@
-use std::fs;
+use std::env;
+use std::cell::RefCell;
 use std::cmp;
-use aes::Aes128;
-use aes::cipher::{
-    BlockEncrypt, NewBlockCipher,
-    generic_array::GenericArray
-};
-use hex;
+use std::fs;
+use std::process;
+use std::rc::Rc;
+use rand_core::{OsRng, RngCore};
+use cipher_cli::openssl::EncCipher;
+use cipher_cli::{kdf, openssl, output};
+
+mod audit;
+mod cipher;
+mod drbg;
+mod input;
+
+// `openssl enc` ciphers of the openssl-* commands, the first one is the default
+const CIPHERS: &[EncCipher] = &[EncCipher::Aes128Ctr, EncCipher::Aes256Ctr];
+
+const USAGE: &str = "usage: cargo run [-- <command> <args>]
+commands:
+    decrypt [--output mode] [--out-dir dir] [passphrase options] [input_json | ciphertexts_file keys_file]
+    audit [input_json | ciphertexts_file keys_file]
+    drbg [--aes128] [--no-df] [--prediction-resistance] [--personalization hex] n_bytes [entropy_hex [nonce_hex]] [steps]
+    openssl-decrypt [-a] [--cipher aes-128-ctr|aes-256-ctr] (-K key_hex -iv iv_hex | passphrase options) [--output mode] [--out-dir dir] in_file
+    openssl-encrypt [-a] [--cipher aes-128-ctr|aes-256-ctr] (-K key_hex -iv iv_hex | passphrase options) in_file out_file
+passphrase options:
+    --passphrase p [--salt hex] [--kdf pbkdf2|evp] [--iterations n] [--md sha256|md5] [--hkdf-info label]
+drbg steps, run in order after the instantiation:
+    --generate additional_input_hex | --reseed entropy_hex additional_input_hex";
 
 /**
  * Bitwise xor of two binary strings (represented as byte vectors)
  */
-fn xor_bytes(bin_1: &Vec<u8>, bin_2: &Vec<u8>) -> Vec<u8> {
-    let bin_xored: Vec<u8> = bin_1.iter()
+fn xor_bytes(bin_1: &[u8], bin_2: &[u8]) -> Vec<u8> {
+    bin_1.iter()
         .zip(bin_2)
         .map(|(x1, x2)| x1 ^ x2)
-        .collect();
-    
-    return bin_xored;
-}
-
-/**
- * Returns String based on provided binary string representing ascii text
- */
-fn bytes_to_ascii(bytes: Vec<u8>) -> String {
-    let plaintext_chars: Vec<char> = bytes.iter().map(|x| *x as char).collect();
-    let plaintext: String = plaintext_chars.iter().cloned().collect::<String>();
-    return plaintext;
+        .collect()
 }
 
 /**
  * Increments a binary string (represented by a bytes vector)
  */
-fn increment_nonce(nonce_orig: &Vec<u8>) -> Vec<u8> {
+fn increment_nonce(nonce_orig: &[u8]) -> Vec<u8> {
 
-    let mut nonce_new = nonce_orig.clone();
+    let mut nonce_new = nonce_orig.to_vec();
 
     // check if the increment overflows to next bytes, break otherwise
     for i in (0..16).rev() {
@@ -45,28 +55,24 @@ fn increment_nonce(nonce_orig: &Vec<u8>) -> Vec<u8> {
             break;
         }
     }
-    return nonce_new;
+    nonce_new
 }
 
 /**
- * Decrypts CTR ciphertext
+ * Decrypts CTR ciphertext (prefixed with the nonce)
  */
-fn decrypt(key_hex: String, ciphertext_hex: String) -> String {
-
-    // convert all to byte arrays
-    let key: Vec<u8> = hex::decode(key_hex).unwrap();
-    let ciphertext: Vec<u8> = hex::decode(ciphertext_hex).unwrap();
+fn decrypt(key: &[u8], ciphertext: &[u8]) -> Vec<u8> {
 
     // get nonce (iv)
     let mut nonce = ciphertext[0..16].to_vec();
 
     // initialize cipher
-    let cipher = Aes128::new(&GenericArray::from_slice(&key));
+    let cipher = cipher::Cipher::new(key);
 
     // run the decryption algorithm
     let n_blocks: i32 = ((ciphertext.len() as f64) / 16.0).ceil() as i32 - 1;
 
-    // convert message to ascii and return it
+    // decrypt block by block
     let mut message: Vec<u8> = Vec::new();
     for i in 0..n_blocks {
 
@@ -76,9 +82,9 @@ fn decrypt(key_hex: String, ciphertext_hex: String) -> String {
         let ciphertext_block = ciphertext[block_start..block_end].to_vec();
 
         // decrypt block and add it to message
-        let mut nonce_mutable = GenericArray::clone_from_slice(&nonce);
-        cipher.encrypt_block(& mut nonce_mutable);
-        let decrypted_block = xor_bytes(&nonce_mutable.to_vec(), &ciphertext_block);
+        let mut nonce_mutable = nonce.clone();
+        cipher.encrypt_block(&mut nonce_mutable);
+        let decrypted_block = xor_bytes(&nonce_mutable, &ciphertext_block);
         message.extend(decrypted_block);
 
         // increment nonce
@@ -86,30 +92,400 @@ fn decrypt(key_hex: String, ciphertext_hex: String) -> String {
     }
 
     // return plaintext decrypted message
-    return bytes_to_ascii(message);
+    message
 }
 
 /**
- * Reads a file and returns vector of lines from the file
+ * Encrypts the message in CTR mode, returns the nonce-prefixed ciphertext
  */
-fn read_lines(path: &str) -> Vec<String> {
-    let content_raw = fs::read_to_string(path)
-        .expect(format!("Please provide {} file with lines of hex strings in the project root", path).as_str());
+fn encrypt(key: &[u8], nonce: &[u8], message: &[u8]) -> Vec<u8> {
 
-    return content_raw.split('\n').map(|x| x.to_string()).collect();
+    // the keystream is the same in both directions
+    let mut nonce_message = nonce.to_vec();
+    nonce_message.extend(message);
+
+    let mut ciphertext = nonce.to_vec();
+    ciphertext.extend(decrypt(key, &nonce_message));
+    ciphertext
 }
 
-fn main() {
-    // load ciphertexts and keys
-    let ciphertexts: Vec<String> = read_lines("./input_ciphertexts.txt");
-    let keys: Vec<String> = read_lines("./input_keys.txt");
+/**
+ * Reads the input (structured file or legacy pair of files), exits with status 1 if it is invalid
+ */
+fn read_input_or_exit(paths: &[String], key_derivation: Option<&kdf::KeyDerivation>) -> Vec<input::Message> {
+    input::read_input(paths, key_derivation).unwrap_or_else(|error| {
+        eprintln!("Invalid input: {}", error);
+        process::exit(1);
+    })
+}
 
-    // for each pair, dedcrypt the message and print the acsii plaintext
-    for (index, (ciphertext, key)) in ciphertexts.into_iter().zip(keys).enumerate() {
-        
-        let message: String = decrypt(key, ciphertext);
+/**
+ * Decrypts every message of the input and prints the plaintexts
+ * Exits with status 1 if any plaintext differs from the expected one
+ */
+fn run_decrypt(args: &[String]) {
+    let (output_mode, key_derivation, paths) = output::parse_output_args(args)
+        .and_then(|(output_mode, args)| {
+            let (key_derivation, paths) = kdf::KeyDerivation::from_args(&args)?;
+            Ok((output_mode, key_derivation, paths))
+        })
+        .unwrap_or_else(|error| {
+            eprintln!("Invalid arguments: {}", error);
+            process::exit(2);
+        });
+    let messages = read_input_or_exit(&paths, key_derivation.as_ref());
+
+    // for each message, decrypt it and output the plaintext
+    let mut mismatches = 0;
+    for (index, message) in messages.iter().enumerate() {
+
+        let plaintext: Vec<u8> = decrypt(&message.key, &message.ciphertext);
 
         println!("\nEncrypted message number {}:", index);
-        println!("{}", message);
-    } 
+        output::write_plaintext(&plaintext, index, &output_mode).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+
+        if let Some(expected) = &message.expected_plaintext {
+            if plaintext != expected.as_bytes() {
+                println!("Plaintext does not match the expected plaintext");
+                mismatches += 1;
+            }
+        }
+    }
+
+    if mismatches > 0 {
+        process::exit(1);
+    }
+}
+
+/**
+ * Checks the input for reused nonces and overlapping counter ranges
+ * Exits with status 1 if any violation is found
+ */
+fn run_audit(args: &[String]) {
+    let messages = read_input_or_exit(args, None);
+
+    let findings = audit::audit_nonces(&messages);
+    for finding in &findings {
+        println!("{}", finding);
+    }
+
+    if findings.is_empty() {
+        println!("No nonce violations found");
+    } else {
+        println!("{} nonce violation(s) found", findings.len());
+        process::exit(1);
+    }
+}
+
+/**
+ * Request to the DRBG after its instantiation, the drbg command runs them in the order of the arguments
+ */
+enum DrbgStep {
+    // additional input
+    Generate(Vec<u8>),
+    // entropy and additional input
+    Reseed(Vec<u8>, Vec<u8>),
+}
+
+/**
+ * Arguments of the drbg command
+ */
+struct DrbgArgs {
+    cipher: drbg::DrbgCipher,
+    key_length: usize,
+    derivation_function: bool,
+    prediction_resistance: bool,
+    personalization: Vec<u8>,
+    n_bytes: usize,
+    // seeded from the OS when not given
+    entropy: Option<Vec<u8>>,
+    nonce: Option<Vec<u8>>,
+    steps: Vec<DrbgStep>,
+}
+
+fn decode_hex(value: &str, name: &str) -> Result<Vec<u8>, String> {
+    hex::decode(value).map_err(|x| format!("{} is not a valid hex string: {}", name, x))
+}
+
+/**
+ * Parses the arguments of the drbg command
+ * Without --generate a single request without additional input follows the other steps
+ */
+fn parse_drbg_args(args: &[String]) -> Result<DrbgArgs, String> {
+    let mut aes128 = false;
+    let mut derivation_function = true;
+    let mut prediction_resistance = false;
+    let mut personalization: Vec<u8> = Vec::new();
+    let mut steps: Vec<DrbgStep> = Vec::new();
+    let mut positional: Vec<&String> = Vec::new();
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        let mut value = || args_iter.next().ok_or(format!("{} requires a value", arg));
+        match arg.as_str() {
+            "--aes128" => aes128 = true,
+            "--no-df" => derivation_function = false,
+            "--prediction-resistance" => prediction_resistance = true,
+            "--personalization" => personalization = decode_hex(value()?, "personalization")?,
+            "--generate" => steps.push(DrbgStep::Generate(decode_hex(value()?, "additional input")?)),
+            "--reseed" => {
+                let entropy = decode_hex(value()?, "reseed entropy")?;
+                steps.push(DrbgStep::Reseed(entropy, decode_hex(value()?, "additional input")?));
+            }
+            other if other.starts_with("--") => return Err(format!("unknown option {}", other)),
+            _ => positional.push(arg),
+        }
+    }
+    if !steps.iter().any(|x| matches!(x, DrbgStep::Generate(_))) {
+        steps.push(DrbgStep::Generate(Vec::new()));
+    }
+
+    let (cipher, key_length) = match aes128 {
+        true => (drbg::DrbgCipher::Aes128, 16),
+        false => (drbg::DrbgCipher::Aes256, 32),
+    };
+    let n_bytes: usize = positional.first()
+        .ok_or("missing n_bytes")?
+        .parse()
+        .map_err(|x| format!("invalid n_bytes: {}", x))?;
+
+    // entropy has the security strength with df and the full seed length without it
+    let entropy_length = match derivation_function {
+        true => key_length,
+        false => key_length + 16,
+    };
+    let entropy = positional.get(1).map(|x| decode_hex(x, "entropy")).transpose()?;
+    let reseed_entropy = steps.iter().filter_map(|x| match x {
+        DrbgStep::Reseed(entropy, _) => Some(entropy),
+        DrbgStep::Generate(_) => None,
+    });
+    if let Some(entropy) = entropy.iter().chain(reseed_entropy).find(|x| x.len() != entropy_length) {
+        return Err(format!("entropy must be {} bytes long, got {}", entropy_length, entropy.len()));
+    }
+    let nonce = positional.get(2).map(|x| decode_hex(x, "nonce")).transpose()?;
+    if positional.len() > 3 {
+        return Err(format!("unexpected argument {}", positional[3]));
+    }
+
+    Ok(DrbgArgs { cipher, key_length, derivation_function, prediction_resistance, personalization, n_bytes, entropy, nonce, steps })
+}
+
+/**
+ * Instantiates the DRBG and prints the output of every generate request in hex, one request per line
+ * Given entropy makes the output reproducible (e.g. to run the CAVP flow), otherwise the DRBG is seeded from the OS
+ */
+fn run_drbg(args: &[String]) {
+    let args = parse_drbg_args(args).unwrap_or_else(|error| {
+        eprintln!("Invalid arguments: {}", error);
+        process::exit(2);
+    });
+
+    // entropy handed out on the next (re)seed, set by the arguments before each of them
+    let next_entropy: Rc<RefCell<Option<Vec<u8>>>> = Rc::new(RefCell::new(args.entropy.clone()));
+    let source_entropy = Rc::clone(&next_entropy);
+    let entropy_source: drbg::EntropySource = Box::new(move |dest: &mut [u8]| match &*source_entropy.borrow() {
+        Some(entropy) => dest.copy_from_slice(entropy),
+        None => OsRng.fill_bytes(dest),
+    });
+    let nonce = args.nonce.clone().unwrap_or_else(|| match args.entropy {
+        Some(_) => Vec::new(),
+        None => {
+            let mut nonce = vec![0u8; args.key_length / 2];
+            OsRng.fill_bytes(&mut nonce);
+            nonce
+        }
+    });
+
+    let result = drbg::CtrDrbg::new(
+        args.cipher, args.derivation_function, args.prediction_resistance, entropy_source, &nonce, &args.personalization
+    ).and_then(|mut rng| {
+        for step in &args.steps {
+            match step {
+                DrbgStep::Generate(additional_input) => {
+                    // longer outputs are split into requests of the maximum length, each with the additional input
+                    let mut output = vec![0u8; args.n_bytes];
+                    for chunk in output.chunks_mut(drbg::MAX_BYTES_PER_REQUEST) {
+                        rng.generate(chunk, additional_input)?;
+                    }
+                    println!("{}", hex::encode(output));
+                }
+                DrbgStep::Reseed(entropy, additional_input) => {
+                    *next_entropy.borrow_mut() = Some(entropy.clone());
+                    rng.reseed(additional_input)?;
+                }
+            }
+        }
+        Ok(())
+    });
+
+    if let Err(error) = result {
+        eprintln!("Invalid arguments: {}", error);
+        process::exit(2);
+    }
+}
+
+/**
+ * Parses arguments of the openssl-* commands, exits with status 2 if they are invalid
+ */
+fn parse_openssl_args_or_exit(args: &[String]) -> (openssl::OpensslOptions, Vec<String>) {
+    openssl::parse_openssl_args(args, CIPHERS).unwrap_or_else(|error| {
+        eprintln!("Invalid arguments: {}", error);
+        process::exit(2);
+    })
+}
+
+/**
+ * Decrypts the contents of a file produced by `openssl enc -aes-128-ctr` or `-aes-256-ctr`
+ */
+fn decrypt_openssl(data: &[u8], options: &openssl::OpensslOptions) -> Result<Vec<u8>, String> {
+    let container = openssl::parse_container(data, options.base64)?;
+    let (key, mut nonce_ciphertext) = options.key_iv(container.salt.as_deref())?;
+    nonce_ciphertext.extend(container.ciphertext);
+    Ok(decrypt(&key, &nonce_ciphertext))
+}
+
+/**
+ * Decrypts a file produced by `openssl enc` and outputs the plaintext
+ */
+fn run_openssl_decrypt(args: &[String]) {
+    let (output_mode, args) = output::parse_output_args(args).unwrap_or_else(|error| {
+        eprintln!("Invalid arguments: {}", error);
+        process::exit(2);
+    });
+    let (options, paths) = parse_openssl_args_or_exit(&args);
+    if paths.len() != 1 {
+        eprintln!("Expected exactly one input file");
+        process::exit(2);
+    }
+
+    let result = fs::read(&paths[0])
+        .map_err(|x| format!("unable to read {}: {}", paths[0], x))
+        .and_then(|data| decrypt_openssl(&data, &options))
+        .and_then(|plaintext| output::write_plaintext(&plaintext, 0, &output_mode));
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+/**
+ * Encrypts a message into the `openssl enc -aes-128-ctr` or `-aes-256-ctr` format
+ */
+fn encrypt_openssl(message: &[u8], options: &openssl::OpensslOptions) -> Result<Vec<u8>, String> {
+    let salt = options.new_salt()?;
+    let (key, nonce) = options.key_iv(salt.as_deref())?;
+
+    // openssl does not store the nonce, it is derived (or given) on decryption
+    let ciphertext = encrypt(&key, &nonce, message)[16..].to_vec();
+    Ok(openssl::serialize_container(&openssl::Container { salt, ciphertext }, options.base64))
+}
+
+/**
+ * Encrypts a file into the `openssl enc -aes-128-ctr` or `-aes-256-ctr` format
+ */
+fn run_openssl_encrypt(args: &[String]) {
+    let (options, paths) = parse_openssl_args_or_exit(args);
+    if paths.len() != 2 {
+        eprintln!("Expected an input file and an output file");
+        process::exit(2);
+    }
+
+    let result = fs::read(&paths[0])
+        .map_err(|x| format!("unable to read {}: {}", paths[0], x))
+        .and_then(|message| encrypt_openssl(&message, &options))
+        .and_then(|data| fs::write(&paths[1], data).map_err(|x| format!("unable to write {}: {}", paths[1], x)));
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(|x| x.as_str()) {
+        None => run_decrypt(&[]),
+        Some("decrypt") => run_decrypt(&args[2..]),
+        Some("audit") => run_audit(&args[2..]),
+        Some("drbg") => run_drbg(&args[2..]),
+        Some("openssl-decrypt") => run_openssl_decrypt(&args[2..]),
+        Some("openssl-encrypt") => run_openssl_encrypt(&args[2..]),
+        Some(command) => {
+            eprintln!("Unknown command {}\n{}", command, USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(args: &[&str]) -> openssl::OpensslOptions {
+        let args: Vec<String> = args.iter().map(|x| x.to_string()).collect();
+        let (options, remaining) = openssl::parse_openssl_args(&args, CIPHERS).unwrap();
+        assert!(remaining.is_empty());
+        options
+    }
+
+    // files written by openssl enc from fixtures/plaintext.txt and the options that decrypt them
+    const FIXTURES: &[(&str, &[&str])] = &[
+        ("aes-128-ctr-pbkdf2.bin", &["--passphrase", "fixture"]),
+        ("aes-128-ctr-pbkdf2.b64", &["-a", "--passphrase", "fixture"]),
+        ("aes-128-ctr-md5.bin", &["--passphrase", "fixture", "--kdf", "evp", "--md", "md5"]),
+        ("aes-128-ctr-key-iv.bin", &["-K", "000102030405060708090a0b0c0d0e0f", "-iv", "f0e0d0c0b0a090807060504030201000"]),
+        ("aes-256-ctr-pbkdf2.bin", &["--cipher", "aes-256-ctr", "--passphrase", "fixture"]),
+        ("aes-256-ctr-md5.bin", &["--cipher", "aes-256-ctr", "--passphrase", "fixture", "--kdf", "evp", "--md", "md5"]),
+        ("aes-256-ctr-key-iv.bin", &["--cipher", "aes-256-ctr",
+            "-K", "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f", "-iv", "f0e0d0c0b0a090807060504030201000"]),
+    ];
+
+    #[test]
+    fn openssl_fixtures() {
+        let plaintext = fs::read("fixtures/plaintext.txt").unwrap();
+        for (name, args) in FIXTURES {
+            let data = fs::read(format!("fixtures/{}", name)).unwrap();
+            assert_eq!(decrypt_openssl(&data, &options(args)).unwrap(), plaintext, "{}", name);
+        }
+    }
+
+    #[test]
+    fn openssl_round_trip() {
+        let plaintext = fs::read("fixtures/plaintext.txt").unwrap();
+        for (name, args) in FIXTURES {
+            // a random salt every time
+            let data = encrypt_openssl(&plaintext, &options(args)).unwrap();
+            assert_eq!(decrypt_openssl(&data, &options(args)).unwrap(), plaintext, "{}", name);
+
+            // with the salt of the fixture, the file openssl wrote
+            let fixture = fs::read(format!("fixtures/{}", name)).unwrap();
+            let mut args: Vec<&str> = args.to_vec();
+            let salt = openssl::parse_container(&fixture, args.contains(&"-a")).unwrap().salt.map(hex::encode);
+            if let Some(salt) = &salt {
+                args.extend(["--salt", salt]);
+            }
+            assert_eq!(encrypt_openssl(&plaintext, &options(&args)).unwrap(), fixture, "{}", name);
+        }
+    }
+
+    #[test]
+    fn openssl_rejects_mismatched_options() {
+        let args = |x: &[&str]| -> Vec<String> { x.iter().map(|x| x.to_string()).collect() };
+        let iv = "f0e0d0c0b0a090807060504030201000";
+
+        // the key length must match the cipher
+        assert!(openssl::parse_openssl_args(&args(&["--cipher", "aes-256-ctr", "-K", "000102030405060708090a0b0c0d0e0f", "-iv", iv]), CIPHERS).is_err());
+        assert!(openssl::parse_openssl_args(&args(&["--cipher", "aes-192-ctr", "--passphrase", "fixture"]), CIPHERS).is_err());
+
+        // a salted file needs the passphrase, an unsalted one the key
+        let salted = fs::read("fixtures/aes-128-ctr-pbkdf2.bin").unwrap();
+        assert!(decrypt_openssl(&salted, &options(&["-K", "000102030405060708090a0b0c0d0e0f", "-iv", iv])).is_err());
+        let unsalted = fs::read("fixtures/aes-128-ctr-key-iv.bin").unwrap();
+        assert!(decrypt_openssl(&unsalted, &options(&["--passphrase", "fixture"])).is_err());
+    }
 }
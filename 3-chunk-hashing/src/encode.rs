@@ -0,0 +1,167 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use crate::hash::{HashFunction, Parameters};
+
+/**
+ * Reads the chunks of the input from the last one to the first one, one chunk in memory at a time
+ * Chunks are aligned to the start of the input, so the last chunk is the short one
+ * Calls visit with every chunk, returns the length of the input
+ */
+pub fn for_each_chunk_backwards<R: Read + Seek, F: FnMut(&[u8])>(
+    reader: &mut R,
+    max_chunk_length: usize,
+    mut visit: F
+) -> io::Result<u64> {
+    let sequence_length = reader.seek(SeekFrom::End(0))?;
+    let mut chunk = vec![0u8; max_chunk_length];
+    let mut chunk_end = sequence_length;
+
+    while chunk_end > 0 {
+        let chunk_length = match chunk_end % max_chunk_length as u64 {
+            0 => max_chunk_length,
+            n => n as usize
+        };
+
+        // read_exact fills the whole chunk or fails, unlike a single read call
+        reader.seek(SeekFrom::Start(chunk_end - chunk_length as u64))?;
+        reader.read_exact(&mut chunk[..chunk_length])?;
+        visit(&chunk[..chunk_length]);
+
+        chunk_end -= chunk_length as u64;
+    }
+    Ok(sequence_length)
+}
+
+/**
+ * Returns hash of the provided chunk
+ */
+pub fn hash_chunk(chunk: &[u8], hash_function: HashFunction) -> Vec<u8> {
+    hash_function.hash(chunk)
+}
+
+/**
+ * Performs the sequential hashing algorithm, reading the input backwards in bounded memory
+ * Outputs H0 (hash of the first chunk concatenated with H1)
+ */
+pub fn hash_sequence<R: Read + Seek>(reader: &mut R, parameters: &Parameters) -> io::Result<Vec<u8>> {
+
+    // initialize hash as empty
+    let mut hash: Vec<u8> = vec![];
+
+    for_each_chunk_backwards(reader, parameters.chunk_length, |chunk| {
+        // append the hash of the following chunk
+        let mut chunk_with_hash = chunk.to_vec();
+        chunk_with_hash.extend(&hash);
+
+        // compute its hash
+        hash = hash_chunk(&chunk_with_hash, parameters.hash);
+    })?;
+
+    // return hash of the first block
+    Ok(hash)
+}
+
+/**
+ * Writes the distributable stream: every chunk followed by the hash of the next augmented chunk
+ * The hashes are computed backwards like in `hash_sequence` and only they are kept in memory,
+ * the chunks are then read again from the start and written with the hashes
+ * The memory is O(n / chunk length): one hash per chunk, e.g. 32 MiB of SHA-256 hashes for 1 GiB in 1 KiB chunks
+ * Returns H0 and the length of the input
+ */
+pub fn write_augmented<R: Read + Seek, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    parameters: &Parameters
+) -> io::Result<(Vec<u8>, u64)> {
+    // hashes of the augmented chunks from the last one to the first, one after another
+    let mut hashes: Vec<u8> = Vec::new();
+    let mut hash: Vec<u8> = vec![];
+
+    let sequence_length = for_each_chunk_backwards(reader, parameters.chunk_length, |chunk| {
+        let mut chunk_with_hash = chunk.to_vec();
+        chunk_with_hash.extend(&hash);
+        hash = hash_chunk(&chunk_with_hash, parameters.hash);
+        hashes.extend(&hash);
+    })?;
+
+    // the i-th item is now the hash of augmented chunk i
+    let mut hashes = hashes.chunks(parameters.hash.output_length()).rev();
+    let h0 = hashes.next().map(|x| x.to_vec()).unwrap_or_default();
+
+    reader.seek(SeekFrom::Start(0))?;
+    let mut chunk = vec![0u8; parameters.chunk_length];
+    let mut remaining = sequence_length;
+    for next_hash in hashes.map(Some).chain([None]) {
+        let chunk_length = remaining.min(parameters.chunk_length as u64) as usize;
+        reader.read_exact(&mut chunk[..chunk_length])?;
+        writer.write_all(&chunk[..chunk_length])?;
+        if let Some(next_hash) = next_hash {
+            writer.write_all(next_hash)?;
+        }
+        remaining -= chunk_length as u64;
+    }
+    writer.flush()?;
+
+    Ok((h0, sequence_length))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::hash::{DEFAULT_CHUNK_LENGTH, DEFAULT_HASH};
+    use crate::random_bytes;
+    use super::*;
+
+    const PARAMETERS: Parameters = Parameters { chunk_length: DEFAULT_CHUNK_LENGTH, hash: DEFAULT_HASH };
+
+    /**
+     * The original algorithm, over the whole sequence in memory
+     */
+    fn hash_in_memory(sequence: &[u8]) -> Vec<u8> {
+        let mut hash: Vec<u8> = vec![];
+        let mut sequence_length = sequence.len();
+        while sequence_length > 0 {
+            let chunk_length = match sequence_length % PARAMETERS.chunk_length {
+                0 => PARAMETERS.chunk_length,
+                n => n
+            };
+            let mut chunk_with_hash = sequence[sequence_length - chunk_length..sequence_length].to_vec();
+            chunk_with_hash.extend(&hash);
+            hash = hash_chunk(&chunk_with_hash, PARAMETERS.hash);
+            sequence_length -= chunk_length;
+        }
+        hash
+    }
+
+    /**
+     * Reader returning at most 7 bytes per read call
+     */
+    struct ShortReader(Cursor<Vec<u8>>);
+
+    impl Read for ShortReader {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let length = buffer.len().min(7);
+            self.0.read(&mut buffer[..length])
+        }
+    }
+
+    impl Seek for ShortReader {
+        fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+            self.0.seek(position)
+        }
+    }
+
+    #[test]
+    fn same_h0_as_in_memory() {
+        let chunk_length = PARAMETERS.chunk_length;
+        for length in [0, 1, chunk_length - 1, chunk_length, chunk_length + 1, 5 * chunk_length, 5000] {
+            let sequence = random_bytes(length);
+            let expected = hash_in_memory(&sequence);
+            assert_eq!(hash_sequence(&mut Cursor::new(&sequence), &PARAMETERS).unwrap(), expected);
+            assert_eq!(hash_sequence(&mut ShortReader(Cursor::new(sequence.clone())), &PARAMETERS).unwrap(), expected);
+
+            let mut stream: Vec<u8> = Vec::new();
+            let (h0, payload_length) = write_augmented(&mut ShortReader(Cursor::new(sequence.clone())), &mut stream, &PARAMETERS).unwrap();
+            assert_eq!((h0, payload_length), (expected, length as u64));
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use blake2::Blake2b512;
+use sha2::{Digest, Sha512, Sha512_256};
+use sha3::Sha3_256;
+
+// the original parameters of the scheme
+pub const DEFAULT_CHUNK_LENGTH: usize = 1024;
+pub const DEFAULT_HASH: HashFunction = HashFunction::Sha256;
+
+/**
+ * Hash function used to chain the chunks
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum HashFunction {
+    Sha256,
+    Sha512,
+    Sha512_256,
+    Sha3_256,
+    Blake2b,
+    Blake3,
+}
+
+impl HashFunction {
+
+    pub fn from_name(name: &str) -> Result<HashFunction, String> {
+        match name.to_lowercase().as_str() {
+            "sha256" => Ok(HashFunction::Sha256),
+            "sha512" => Ok(HashFunction::Sha512),
+            "sha512-256" => Ok(HashFunction::Sha512_256),
+            "sha3-256" => Ok(HashFunction::Sha3_256),
+            "blake2b" => Ok(HashFunction::Blake2b),
+            "blake3" => Ok(HashFunction::Blake3),
+            _ => Err(format!("unknown hash {}, expected sha256, sha512, sha512-256, sha3-256, blake2b or blake3", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashFunction::Sha256 => "sha256",
+            HashFunction::Sha512 => "sha512",
+            HashFunction::Sha512_256 => "sha512-256",
+            HashFunction::Sha3_256 => "sha3-256",
+            HashFunction::Blake2b => "blake2b",
+            HashFunction::Blake3 => "blake3",
+        }
+    }
+
+    /**
+     * Length of the hash in bytes (BLAKE2b is used with its full 64 byte output)
+     */
+    pub fn output_length(&self) -> usize {
+        match self {
+            HashFunction::Sha512 | HashFunction::Blake2b => 64,
+            _ => 32,
+        }
+    }
+
+    /**
+     * Length of the blocks the hash function compresses, the key length of HMAC
+     */
+    pub fn block_length(&self) -> usize {
+        match self {
+            HashFunction::Sha256 | HashFunction::Blake3 => 64,
+            HashFunction::Sha512 | HashFunction::Sha512_256 | HashFunction::Blake2b => 128,
+            HashFunction::Sha3_256 => 136,
+        }
+    }
+
+    /**
+     * SHA-256 comes from the sha2 crate, or from the `sha256` module with the scratch-sha256 feature
+     */
+    pub fn hash(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            #[cfg(not(feature = "scratch-sha256"))]
+            HashFunction::Sha256 => sha2::Sha256::digest(data).to_vec(),
+            #[cfg(feature = "scratch-sha256")]
+            HashFunction::Sha256 => crate::sha256::Sha256::digest(data).to_vec(),
+            HashFunction::Sha512 => Sha512::digest(data).to_vec(),
+            HashFunction::Sha512_256 => Sha512_256::digest(data).to_vec(),
+            HashFunction::Sha3_256 => Sha3_256::digest(data).to_vec(),
+            HashFunction::Blake2b => Blake2b512::digest(data).to_vec(),
+            HashFunction::Blake3 => blake3::hash(data).as_bytes().to_vec(),
+        }
+    }
+}
+
+/**
+ * Chunk length and hash function of a stream
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct Parameters {
+    pub chunk_length: usize,
+    pub hash: HashFunction,
+}
+
+impl Default for Parameters {
+    fn default() -> Parameters {
+        Parameters { chunk_length: DEFAULT_CHUNK_LENGTH, hash: DEFAULT_HASH }
+    }
+}
+
+/**
+ * Parses a chunk size in bytes, optionally with a K/KiB or M/MiB suffix (4KiB = 4096)
+ */
+fn parse_chunk_size(value: &str) -> Result<usize, String> {
+    let lowercase = value.to_lowercase();
+    let (number, multiplier) = match lowercase.trim_end_matches("ib").trim_end_matches('b') {
+        x if x.ends_with('k') => (&x[..x.len() - 1], 1 << 10),
+        x if x.ends_with('m') => (&x[..x.len() - 1], 1 << 20),
+        x => (x, 1),
+    };
+    match number.parse::<usize>() {
+        Ok(0) => Err("chunk size must be positive".to_string()),
+        Ok(n) => n.checked_mul(multiplier).ok_or(format!("chunk size {} is too large", value)),
+        Err(_) => Err(format!("invalid chunk size {}", value)),
+    }
+}
+
+impl Parameters {
+
+    /**
+     * Extracts --chunk-size <size> and --hash <name> from the arguments
+     * Returns the parameters (1024 bytes and SHA-256 by default) and the remaining arguments
+     */
+    pub fn from_args(args: &[String]) -> Result<(Parameters, Vec<String>), String> {
+        let mut parameters = Parameters::default();
+        let mut remaining: Vec<String> = Vec::new();
+
+        let mut args_iter = args.iter();
+        while let Some(arg) = args_iter.next() {
+            let mut value = || args_iter.next().ok_or(format!("{} requires a value", arg));
+            match arg.as_str() {
+                "--chunk-size" => parameters.chunk_length = parse_chunk_size(value()?)?,
+                "--hash" => parameters.hash = HashFunction::from_name(value()?)?,
+                _ => remaining.push(arg.clone()),
+            }
+        }
+        Ok((parameters, remaining))
+    }
+
+    /**
+     * Length of a full augmented chunk (chunk and the hash of the next one)
+     */
+    pub fn block_length(&self) -> usize {
+        self.chunk_length + self.hash.output_length()
+    }
+}
@@ -0,0 +1,99 @@
+use std::thread;
+use std::time::Duration;
+use crate::hash::HashFunction;
+
+const INNER_PAD: u8 = 0x36;
+const OUTER_PAD: u8 = 0x5c;
+
+/**
+ * HMAC (RFC 2104): hash((key ^ opad) || hash((key ^ ipad) || message))
+ * The key is hashed first when it is longer than a block, then padded with zeros to a block
+ */
+pub fn hmac(hash: HashFunction, key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut block_key = match key.len() > hash.block_length() {
+        true => hash.hash(key),
+        false => key.to_vec(),
+    };
+    block_key.resize(hash.block_length(), 0);
+
+    let mut inner: Vec<u8> = block_key.iter().map(|x| x ^ INNER_PAD).collect();
+    inner.extend(message);
+    let mut outer: Vec<u8> = block_key.iter().map(|x| x ^ OUTER_PAD).collect();
+    outer.extend(hash.hash(&inner));
+    hash.hash(&outer)
+}
+
+/**
+ * How a verifier compares the received tag with the expected one
+ */
+#[derive(Clone, Copy)]
+pub enum Comparison {
+    // stops at the first differing byte, the time taken tells how many leading bytes are right
+    EarlyExit,
+    // looks at every byte whatever their values
+    ConstantTime,
+}
+
+/**
+ * Compares the tags, spending byte_delay on every compared byte to make the work per byte measurable over the network
+ */
+pub fn compare(comparison: Comparison, tag: &[u8], expected: &[u8], byte_delay: Duration) -> bool {
+    if tag.len() != expected.len() {
+        return false;
+    }
+
+    match comparison {
+        Comparison::EarlyExit => {
+            for (x, y) in tag.iter().zip(expected) {
+                if x != y {
+                    return false;
+                }
+                thread::sleep(byte_delay);
+            }
+            true
+        }
+        Comparison::ConstantTime => {
+            let mut difference = 0u8;
+            for (x, y) in tag.iter().zip(expected) {
+                difference |= x ^ y;
+                thread::sleep(byte_delay);
+            }
+            difference == 0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn rfc4231() {
+        let cases: Vec<&str> = include_str!("../fixtures/rfc4231.txt").split("\n\n").skip(1).collect();
+        assert_eq!(cases.len(), 7);
+
+        for (i, case) in cases.iter().enumerate() {
+            let field = |name: &str| case.lines()
+                .find_map(|x| x.strip_prefix(name).and_then(|x| x.strip_prefix('=')))
+                .and_then(|x| hex::decode(x).ok())
+                .expect("invalid RFC 4231 fixture");
+
+            for hash in [HashFunction::Sha256, HashFunction::Sha512] {
+                // test case 5 truncates the output to 128 bits
+                let expected = field(hash.name());
+                let tag = hmac(hash, &field("key"), &field("data"));
+                assert_eq!(tag[..expected.len()], expected[..], "test case {} HMAC-{}", i + 1, hash.name());
+            }
+        }
+    }
+
+    #[test]
+    fn comparisons() {
+        for comparison in [Comparison::EarlyExit, Comparison::ConstantTime] {
+            assert!(compare(comparison, b"tag", b"tag", Duration::from_secs(0)));
+            assert!(!compare(comparison, b"tag", b"tab", Duration::from_secs(0)));
+            assert!(!compare(comparison, b"ta", b"tag", Duration::from_secs(0)));
+        }
+    }
+}
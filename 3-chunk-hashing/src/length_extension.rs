@@ -0,0 +1,107 @@
+use std::convert::TryInto;
+use std::ops::RangeInclusive;
+use sha2::Digest;
+use crate::sha256::{self, Sha256, OUTPUT_LENGTH};
+
+/**
+ * Local verifier of `SHA256(secret || message)` tags, the construction the attack breaks
+ * It uses the sha2 crate, the attack only uses the in-repo SHA-256
+ */
+pub struct Oracle {
+    secret: Vec<u8>,
+    pub n_queries: usize,
+}
+
+impl Oracle {
+
+    pub fn new(secret: Vec<u8>) -> Oracle {
+        Oracle { secret, n_queries: 0 }
+    }
+
+    pub fn tag(&self, message: &[u8]) -> [u8; OUTPUT_LENGTH] {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(&self.secret);
+        hasher.update(message);
+        hasher.finalize().into()
+    }
+
+    pub fn verify(&mut self, message: &[u8], tag: &[u8; OUTPUT_LENGTH]) -> bool {
+        self.n_queries += 1;
+        self.tag(message) == *tag
+    }
+}
+
+/**
+ * Message accepted with the tag although it was never tagged by the secret holder
+ */
+pub struct Forgery {
+    pub secret_length: usize,
+    pub message: Vec<u8>,
+    pub tag: [u8; OUTPUT_LENGTH],
+}
+
+/**
+ * Forges the tag of message || glue padding || suffix from the tag of message, assuming the secret is secret_length bytes long
+ * The tag is the SHA-256 state after secret || message || glue padding, hashing simply continues from it with the suffix
+ */
+pub fn extend(message: &[u8], tag: &[u8; OUTPUT_LENGTH], secret_length: usize, suffix: &[u8]) -> Forgery {
+    let glue = sha256::padding((secret_length + message.len()) as u64);
+    let mut forged_message = message.to_vec();
+    forged_message.extend(&glue);
+
+    let mut hasher = Sha256::from_state(sha256::digest_to_state(tag), (secret_length + forged_message.len()) as u64);
+    hasher.update(suffix);
+    forged_message.extend(suffix);
+
+    Forgery { secret_length, message: forged_message, tag: hasher.finalize() }
+}
+
+/**
+ * Tries every secret length of the range until the verifier accepts a forgery, one query per guess
+ */
+pub fn attack<F: FnMut(&[u8], &[u8; OUTPUT_LENGTH]) -> bool>(
+    message: &[u8],
+    tag: &[u8],
+    secret_lengths: RangeInclusive<usize>,
+    suffix: &[u8],
+    mut verify: F
+) -> Result<Forgery, String> {
+    let tag: &[u8; OUTPUT_LENGTH] = tag.try_into().map_err(|_| format!("tag is not {} bytes long", OUTPUT_LENGTH))?;
+    let (min_length, max_length) = (*secret_lengths.start(), *secret_lengths.end());
+
+    secret_lengths
+        .map(|secret_length| extend(message, tag, secret_length, suffix))
+        .find(|forgery| verify(&forgery.message, &forgery.tag))
+        .ok_or(format!("no secret length between {} and {} bytes was accepted", min_length, max_length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &[u8] = b"comment=hello;user=guest";
+    const SUFFIX: &[u8] = b";admin=true";
+
+    #[test]
+    fn forgeries() {
+        // secrets around the block boundaries, the glue padding takes one or two blocks
+        for secret_length in 0..=80 {
+            let mut oracle = Oracle::new((0..secret_length).map(|x| x as u8).collect());
+            let tag = oracle.tag(MESSAGE);
+            let forgery = attack(MESSAGE, &tag, 0..=80, SUFFIX, |forged, forged_tag| oracle.verify(forged, forged_tag)).unwrap();
+
+            assert_eq!(forgery.secret_length, secret_length);
+            assert_eq!(oracle.n_queries, secret_length + 1);
+            assert!(forgery.message.starts_with(MESSAGE) && forgery.message.ends_with(SUFFIX));
+            assert!(oracle.verify(&forgery.message, &forgery.tag));
+        }
+    }
+
+    #[test]
+    fn secret_outside_the_range() {
+        let mut oracle = Oracle::new(vec![0u8; 20]);
+        let tag = oracle.tag(MESSAGE);
+        assert!(attack(MESSAGE, &tag, 0..=19, SUFFIX, |forged, forged_tag| oracle.verify(forged, forged_tag)).is_err());
+        assert!(attack(MESSAGE, &tag[..31], 0..=64, SUFFIX, |_, _| true).is_err());
+    }
+}
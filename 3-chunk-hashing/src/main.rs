@@ -1,77 +1,791 @@
-use std::io::Read;
-use std::fs::File;
-use std::fs;
+use std::env;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom};
+use std::fs::{self, File};
+use std::path::Path;
+use std::process;
 
-use sha2::{Sha256, Digest};
-use hex;
+use hash::{HashFunction, Parameters};
+use manifest::{Manifest, Scheme};
+use merkle::MerkleTree;
 
+mod encode;
+mod hash;
+mod hmac;
+mod length_extension;
+mod manifest;
+mod merkle;
+mod sha256;
+mod signing;
+mod state;
+mod timing;
+mod transport;
+mod verify;
+
+const USAGE: &str = "usage: cargo run [-- <command> <args>]
+commands:
+    hash [parameters] [file ...]
+    encode [parameters] in_file out_file [manifest_file]
+    verify (h0_hex [parameters] | --manifest manifest_file [--public-key key_file]) [--state state_file --output out_file] [stream_file]
+    serve [parameters] [--port port] [--corrupt-block index] stream_file
+    fetch (h0_hex [parameters] | --manifest manifest_file [--public-key key_file]) [--port port] [--state state_file --output out_file]
+    merkle-root [parameters] in_file [manifest_file]
+    merkle-proof [parameters] in_file index proof_file [chunk_file]
+    merkle-verify [--public-key key_file] manifest_file index chunk_file proof_file
+    length-extension [--message text] [--append text] [--secret-lengths min-max]
+    hmac [--hash name] key_hex [file]
+    tag-server [--hash name] [--tag-length n] [--delay-us n] [--constant-time] [--port port]
+    timing-attack [--message text] [--hash name] [--tag-length n] [--samples n] [--port port]
+    keygen secret_key_file public_key_file
+    sign manifest_file secret_key_file
+    verify-manifest manifest_file public_key_file
+parameters:
+    --chunk-size bytes (e.g. 4096, 4KiB, 64KiB; default 1024)
+    --hash sha256|sha512|sha512-256|sha3-256|blake2b|blake3 (default sha256)";
+
+/**
+ * Parses the parameters of a command, exits with status 2 on invalid ones
+ */
+fn parameters_from_args(args: &[String]) -> (Parameters, Vec<String>) {
+    Parameters::from_args(args).unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        process::exit(2);
+    })
+}
+
+/**
+ * Name of the file at path for the manifest, None when it cannot be written on a manifest line
+ */
+fn file_name(path: &str) -> Option<String> {
+    Path::new(path).file_name()
+        .and_then(|x| x.to_str())
+        .filter(|x| manifest::is_valid_name(x))
+        .map(|x| x.to_string())
+}
+
+/**
+ * Writes the augmented stream of the input file and its manifest (out_file.manifest by default)
+ * The manifest records the chunk length and the hash function next to H0
+ */
+fn run_encode(args: &[String]) {
+    let (parameters, args) = parameters_from_args(args);
+    let (in_path, out_path, manifest_path) = match args.as_slice() {
+        [in_path, out_path] => (in_path, out_path, format!("{}.manifest", out_path)),
+        [in_path, out_path, manifest_path] => (in_path, out_path, manifest_path.clone()),
+        _ => {
+            eprintln!("Expected an input file and an output file\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let result = File::open(in_path)
+        .map_err(|x| format!("unable to read {}: {}", in_path, x))
+        .and_then(|mut reader| {
+            // the H0 of an empty input is empty, a manifest cannot hold it
+            match reader.metadata() {
+                Ok(metadata) if metadata.len() == 0 => return Err(format!("{} is empty, there is nothing to encode", in_path)),
+                _ => (),
+            }
+            let mut writer = File::create(out_path)
+                .map(BufWriter::new)
+                .map_err(|x| format!("unable to write {}: {}", out_path, x))?;
+            let (h0, payload_length) = encode::write_augmented(&mut reader, &mut writer, &parameters)
+                .map_err(|x| format!("unable to encode {} into {}: {}", in_path, out_path, x))?;
+
+            let manifest = Manifest {
+                scheme: Scheme::Chain,
+                trusted_hash: h0,
+                chunk_length: parameters.chunk_length,
+                hash: parameters.hash,
+                payload_length,
+                name: file_name(in_path),
+                signature: None,
+            };
+            manifest.write(&manifest_path)?;
+            Ok(manifest)
+        });
+
+    match result {
+        Ok(manifest) => println!("H0: {}\nManifest written to {}", hex::encode(&manifest.trusted_hash), manifest_path),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/**
+ * Verifies a stream of blocks (a file or stdin) against H0, verified payload is written to stdout immediately
+ * With --state and --output the payload goes to the output file and the verification can be resumed
+ * The parameters come from the manifest, or from the options when H0 is given directly
+ * Exits with status 1 at the first block which fails the verification
+ */
+fn run_verify(args: &[String]) {
+    let (resumable, args) = resumable_from_args(args);
+    let (h0, parameters, args) = chain_from_args(&args);
+
+    if let Some((state_path, output_path)) = resumable {
+        let stream_path = args.first().unwrap_or_else(|| {
+            eprintln!("A resumable verification needs a stream file\n{}", USAGE);
+            process::exit(2);
+        });
+        let result = state::resume(&h0, &parameters, &state_path, &output_path, |offset| {
+            let mut file = File::open(stream_path)?;
+            file.seek(SeekFrom::Start(offset))?;
+            Ok(file)
+        });
+        exit_with_resumed(result);
+    }
+
+    let stdout = io::stdout();
+    let result = match args.first() {
+        Some(path) => File::open(path)
+            .map_err(verify::VerifyError::from)
+            .and_then(|file| verify::verify_stream(&h0, &parameters, file, stdout.lock())),
+        None => verify::verify_stream(&h0, &parameters, io::stdin().lock(), stdout.lock()),
+    };
+
+    match result {
+        Ok(n_bytes) => eprintln!("Verified {} bytes", n_bytes),
+        Err(error) => {
+            eprintln!("Verification failed: {}", error);
+            process::exit(1);
+        }
+    }
+}
 
 /**
- * Reads contents of the file at path into a bytes vector
+ * Extracts --state path and --output path, which go together
  */
-fn read_binary(path: &str) -> Vec<u8> {
+fn resumable_from_args(args: &[String]) -> (Option<(String, String)>, Vec<String>) {
+    let (state_path, args) = option_from_args(args, "--state");
+    let (output_path, args) = option_from_args(&args, "--output");
+    match (state_path, output_path) {
+        (Some(state_path), Some(output_path)) => (Some((state_path, output_path)), args),
+        (None, None) => (None, args),
+        _ => {
+            eprintln!("--state and --output go together\n{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+/**
+ * Reports the end of a resumable verification, exits with status 1 when it failed
+ */
+fn exit_with_resumed(result: Result<verify::ChainState, String>) -> ! {
+    match result {
+        Ok(state) => {
+            eprintln!("Verified {} bytes", state.payload_length);
+            process::exit(0);
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/**
+ * Extracts the value of the option name from the arguments, returns it and the remaining arguments
+ */
+fn option_from_args(args: &[String], name: &str) -> (Option<String>, Vec<String>) {
+    match args.iter().position(|x| x == name) {
+        Some(i) if i + 1 < args.len() => {
+            let mut remaining = args.to_vec();
+            let value = remaining.drain(i..i + 2).nth(1);
+            (value, remaining)
+        }
+        Some(_) => {
+            eprintln!("{} requires a value\n{}", name, USAGE);
+            process::exit(2);
+        }
+        None => (None, args.to_vec()),
+    }
+}
+
+/**
+ * Parses the value of the option name as a number, exits with status 2 on an invalid one
+ */
+fn number_from_args<T: std::str::FromStr>(args: &[String], name: &str) -> (Option<T>, Vec<String>) {
+    let (value, remaining) = option_from_args(args, name);
+    let number = value.map(|value| value.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid value {} for {}\n{}", value, name, USAGE);
+        process::exit(2);
+    }));
+    (number, remaining)
+}
+
+/**
+ * Serves an augmented stream on localhost, optionally corrupting one of its blocks
+ */
+fn run_serve(args: &[String]) {
+    let (parameters, args) = parameters_from_args(args);
+    let (port, args) = number_from_args(&args, "--port");
+    let (corrupt_block, args) = number_from_args::<u64>(&args, "--corrupt-block");
+    let stream_path = match args.as_slice() {
+        [stream_path] => stream_path,
+        _ => {
+            eprintln!("Expected a stream file\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    // flip the first byte of the block, blocks are block_length bytes apart in the stream
+    let corrupt_offset = corrupt_block.map(|block| block * parameters.block_length() as u64);
+    if let Err(error) = transport::serve(stream_path, port.unwrap_or(transport::DEFAULT_PORT), corrupt_offset) {
+        eprintln!("Unable to serve {}: {}", stream_path, error);
+        process::exit(1);
+    }
+}
+
+/**
+ * Downloads an augmented stream from the local server one block at a time,
+ * verified payload is written to stdout immediately and the download stops at the first invalid block
+ * With --state and --output an interrupted download continues from the last verified block
+ */
+fn run_fetch(args: &[String]) {
+    let (port, args) = number_from_args(args, "--port");
+    let port = port.unwrap_or(transport::DEFAULT_PORT);
+    let (resumable, args) = resumable_from_args(&args);
+    let (h0, parameters, args) = chain_from_args(&args);
+    if !args.is_empty() {
+        eprintln!("Unexpected arguments {}\n{}", args.join(" "), USAGE);
+        process::exit(2);
+    }
+
+    if let Some((state_path, output_path)) = resumable {
+        let result = state::resume(&h0, &parameters, &state_path, &output_path, |offset| {
+            transport::RangeReader::connect(port, parameters.block_length(), offset)
+        });
+        exit_with_resumed(result);
+    }
+
+    let stdout = io::stdout();
+    let result = transport::RangeReader::connect(port, parameters.block_length(), 0)
+        .map_err(verify::VerifyError::from)
+        .and_then(|reader| verify::verify_stream(&h0, &parameters, reader, stdout.lock()));
+
+    match result {
+        Ok(n_bytes) => eprintln!("Downloaded and verified {} bytes", n_bytes),
+        Err(error) => {
+            eprintln!("Verification failed: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/**
+ * Extracts H0 and the parameters of a hash chain, from a manifest (--manifest path) or from H0 in hex and the options
+ * With --public-key path the manifest has to be signed by the key
+ * Returns them with the remaining arguments
+ */
+fn chain_from_args(args: &[String]) -> (Vec<u8>, Parameters, Vec<String>) {
+    let (public_key_path, args) = option_from_args(args, "--public-key");
+    let (h0, parameters, args) = match args.as_slice() {
+        [flag, manifest_path, rest @ ..] if flag == "--manifest" => match signing::read_manifest(manifest_path, public_key_path.as_deref()) {
+            Ok(manifest) if manifest.scheme == Scheme::Chain => (manifest.trusted_hash.clone(), manifest.parameters(), rest.to_vec()),
+            Ok(_) => {
+                eprintln!("{} describes a Merkle tree, use merkle-verify", manifest_path);
+                process::exit(1);
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        },
+        _ if public_key_path.is_some() => {
+            eprintln!("--public-key needs a signed manifest\n{}", USAGE);
+            process::exit(2);
+        }
+        [h0, rest @ ..] => {
+            let (parameters, rest) = parameters_from_args(rest);
+            (hex::decode(h0).unwrap_or_default(), parameters, rest)
+        }
+        [] => (Vec::new(), Parameters::default(), Vec::new()),
+    };
+    if h0.len() != parameters.hash.output_length() {
+        eprintln!("Expected H0 as {} bytes in hex\n{}", parameters.hash.output_length(), USAGE);
+        process::exit(2);
+    }
+    (h0, parameters, args)
+}
+
+/**
+ * Computes H0 of the file at path
+ */
+fn hash_file(path: &str, parameters: &Parameters) -> Vec<u8> {
     let mut f = File::open(path).expect("Please provide the input file");
-    let metadata = fs::metadata(path).expect("unable to read metadata");
-    let mut buffer = vec![0; metadata.len() as usize];
-    f.read(&mut buffer).expect("buffer overflow");
-    buffer
+    encode::hash_sequence(&mut f, parameters).unwrap_or_else(|error| panic!("unable to read {}: {}", path, error))
+}
+
+/**
+ * Prints H0 of the given files (the two input files by default)
+ */
+fn run_hash(args: &[String]) {
+    let (parameters, paths) = parameters_from_args(args);
+    println!("Chunk length {} bytes, hash {}", parameters.chunk_length, parameters.hash.name());
+
+    if paths.is_empty() {
+        // Run the algorithm
+        let hash1 = hash_file("./input1.mp4", &parameters);
+        let hash2 = hash_file("./input2.mp4", &parameters);
+
+        // Print the hashes in hex
+        println!("Input 1 has H0 of : {}", hex::encode(hash1));
+        println!("Input 2 has H0 of : {}", hex::encode(hash2));
+        return;
+    }
+
+    for path in &paths {
+        println!("{} has H0 of : {}", path, hex::encode(hash_file(path, &parameters)));
+    }
+}
+
+/**
+ * Builds the Merkle tree of the input file and writes its root to a manifest (in_file.manifest by default)
+ */
+fn run_merkle_root(args: &[String]) {
+    let (parameters, args) = parameters_from_args(args);
+    let (in_path, manifest_path) = match args.as_slice() {
+        [in_path] => (in_path, format!("{}.manifest", in_path)),
+        [in_path, manifest_path] => (in_path, manifest_path.clone()),
+        _ => {
+            eprintln!("Expected an input file\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let result = File::open(in_path)
+        .and_then(|mut file| {
+            let tree = MerkleTree::build(&mut file, &parameters)?;
+            Ok((tree, file.metadata()?.len()))
+        })
+        .map_err(|x| format!("unable to read {}: {}", in_path, x))
+        .and_then(|(tree, payload_length)| {
+            let manifest = Manifest {
+                scheme: Scheme::Merkle,
+                trusted_hash: tree.root(),
+                chunk_length: parameters.chunk_length,
+                hash: parameters.hash,
+                payload_length,
+                name: file_name(in_path),
+                signature: None,
+            };
+            manifest.write(&manifest_path)?;
+            Ok((manifest, tree.leaf_count()))
+        });
+
+    match result {
+        Ok((manifest, leaf_count)) => println!("Root of {} chunks: {}\nManifest written to {}",
+            leaf_count, hex::encode(&manifest.trusted_hash), manifest_path),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
 }
 
 /**
- * Returns hash of the provided chunk
+ * Parses a chunk index, exits with status 2 on an invalid one
  */
-fn hash_chunk(chunk: &Vec<u8>) -> Vec<u8> {
-     let mut hasher = Sha256::new();
-     hasher.input(chunk);
-     hasher.result().to_vec()
+fn parse_index(index: &str) -> u64 {
+    index.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid chunk index {}\n{}", index, USAGE);
+        process::exit(2);
+    })
 }
 
 /**
- * Performs the sequential hashing algorithm
- * Outputs H0 (hash of the first chunk concatenated with H1)
+ * Writes the inclusion proof of chunk index of the input file, and the chunk itself when chunk_file is given
  */
-fn hash_sequence(sequence: &Vec<u8>) -> Vec<u8> {
+fn run_merkle_proof(args: &[String]) {
+    let (parameters, args) = parameters_from_args(args);
+    let (in_path, index, proof_path, chunk_path) = match args.as_slice() {
+        [in_path, index, proof_path] => (in_path, parse_index(index), proof_path, None),
+        [in_path, index, proof_path, chunk_path] => (in_path, parse_index(index), proof_path, Some(chunk_path)),
+        _ => {
+            eprintln!("Expected an input file, a chunk index and a proof file\n{}", USAGE);
+            process::exit(2);
+        }
+    };
 
-    // initialize hash as empty
-    let mut hash: Vec<u8> = vec![];
+    let result = File::open(in_path)
+        .and_then(|mut file| Ok((MerkleTree::build(&mut file, &parameters)?, file)))
+        .map_err(|x| format!("unable to read {}: {}", in_path, x))
+        .and_then(|(tree, mut file)| {
+            let proof = tree.proof(index)?;
+            fs::write(proof_path, merkle::proof_to_text(&proof))
+                .map_err(|x| format!("unable to write {}: {}", proof_path, x))?;
 
-    // initialize the sequence length
-    let mut sequence_lenght = sequence.len();
+            if let Some(chunk_path) = chunk_path {
+                let mut chunk = Vec::with_capacity(parameters.chunk_length);
+                file.seek(SeekFrom::Start(index * parameters.chunk_length as u64))
+                    .and_then(|_| file.take(parameters.chunk_length as u64).read_to_end(&mut chunk))
+                    .map_err(|x| format!("unable to read {}: {}", in_path, x))?;
+                fs::write(chunk_path, &chunk).map_err(|x| format!("unable to write {}: {}", chunk_path, x))?;
+            }
+            Ok(proof.len())
+        });
 
-    while sequence_lenght > 0 {
-        // compute chunk lenght
-        let chunk_length = match sequence_lenght % 1024 {
-            0 => 1024,
-            n => n
-        };
+    match result {
+        Ok(n_hashes) => println!("Proof of chunk {} ({} hashes) written to {}", index, n_hashes, proof_path),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/**
+ * Verifies a chunk and its inclusion proof against the root of a Merkle manifest
+ * Exits with status 1 when the chunk is not the one at index
+ */
+fn run_merkle_verify(args: &[String]) {
+    let (public_key_path, args) = option_from_args(args, "--public-key");
+    let (manifest_path, index, chunk_path, proof_path) = match args.as_slice() {
+        [manifest_path, index, chunk_path, proof_path] => (manifest_path, parse_index(index), chunk_path, proof_path),
+        _ => {
+            eprintln!("Expected a manifest, a chunk index, a chunk file and a proof file\n{}", USAGE);
+            process::exit(2);
+        }
+    };
 
-        // Take the last chunk and append the previous hash
-        let mut chunk_with_hash: Vec<u8> = sequence[sequence_lenght-chunk_length..sequence_lenght].to_vec();
-        chunk_with_hash.extend(&hash);
+    let result = signing::read_manifest(manifest_path, public_key_path.as_deref())
+        .and_then(|manifest| match manifest.scheme {
+            Scheme::Merkle => Ok(manifest),
+            Scheme::Chain => Err(format!("{} describes a hash chain, use verify", manifest_path)),
+        })
+        .and_then(|manifest| {
+            let chunk = fs::read(chunk_path).map_err(|x| format!("unable to read {}: {}", chunk_path, x))?;
+            let proof = fs::read_to_string(proof_path)
+                .map_err(|x| format!("unable to read {}: {}", proof_path, x))
+                .and_then(|text| merkle::parse_proof(&text, manifest.hash))?;
 
-        // compute its hash
-        hash = hash_chunk(&chunk_with_hash);
+            let leaf_count = manifest.payload_length.div_ceil(manifest.chunk_length as u64);
+            merkle::verify_proof(manifest.hash, &manifest.trusted_hash, leaf_count, index, &chunk, &proof)?;
+            Ok(chunk.len())
+        });
 
-        // reduce sequence length by chunk length
-        sequence_lenght -= chunk_length;
+    match result {
+        Ok(n_bytes) => println!("Chunk {} ({} bytes) is valid", index, n_bytes),
+        Err(error) => {
+            eprintln!("Verification failed: {}", error);
+            process::exit(1);
+        }
     }
+}
 
-    // return hash of the first block
-    hash
+/**
+ * Generates an Ed25519 key pair for signing manifests
+ */
+fn run_keygen(args: &[String]) {
+    let (secret_key_path, public_key_path) = match args {
+        [secret_key_path, public_key_path] => (secret_key_path, public_key_path),
+        _ => {
+            eprintln!("Expected a secret key file and a public key file\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    match signing::generate_keys(secret_key_path, public_key_path) {
+        Ok(public_key) => println!("Public key: {}", hex::encode(public_key.as_bytes())),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
 }
 
-fn main() {
-    
-    // Load input
-    let input1 = read_binary("./input1.mp4");
-    let input2 = read_binary("./input2.mp4");
+/**
+ * Adds the signature of the manifest with the secret key to it
+ */
+fn run_sign(args: &[String]) {
+    let (manifest_path, secret_key_path) = match args {
+        [manifest_path, secret_key_path] => (manifest_path, secret_key_path),
+        _ => {
+            eprintln!("Expected a manifest and a secret key file\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let result = Manifest::read(manifest_path).and_then(|mut manifest| {
+        signing::sign(&mut manifest, secret_key_path)?;
+        manifest.write(manifest_path)
+    });
+
+    match result {
+        Ok(()) => println!("Signed {}", manifest_path),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/**
+ * Checks the signature of a manifest, exits with status 1 when it is missing or invalid
+ */
+fn run_verify_manifest(args: &[String]) {
+    let (manifest_path, public_key_path) = match args {
+        [manifest_path, public_key_path] => (manifest_path, public_key_path),
+        _ => {
+            eprintln!("Expected a manifest and a public key file\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    match signing::read_manifest(manifest_path, Some(public_key_path)) {
+        Ok(manifest) => print!("Valid signature\n{}", manifest.fields_text()),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
 
-    // Run the algorithm
-    let hash1 = hash_sequence(&input1);
-    let hash2 = hash_sequence(&input2);
+/**
+ * Parses a range of secret lengths like 0-64
+ */
+fn parse_length_range(value: &str) -> Result<std::ops::RangeInclusive<usize>, String> {
+    let range = value.split_once('-')
+        .and_then(|(min, max)| Some((min.parse().ok()?, max.parse().ok()?)))
+        .filter(|(min, max)| min <= max);
+    match range {
+        Some((min, max)) => Ok(min..=max),
+        None => Err(format!("invalid secret length range {}, expected min-max", value)),
+    }
+}
 
-    // Print the hashes in hex
-    println!("Input 1 has H0 of : {}", hex::encode(hash1));
-    println!("Input 2 has H0 of : {}", hex::encode(hash2));
+/**
+ * Forges a SHA256(secret || message) tag for the message with appended data against a local verifier with a random secret
+ * The secret length is guessed within the given range, the command exits with status 1 when the forgery fails
+ */
+fn run_length_extension(args: &[String]) {
+    let (message, args) = option_from_args(args, "--message");
+    let (suffix, args) = option_from_args(&args, "--append");
+    let (secret_lengths, args) = option_from_args(&args, "--secret-lengths");
+    if !args.is_empty() {
+        eprintln!("Unexpected arguments {}\n{}", args.join(" "), USAGE);
+        process::exit(2);
+    }
+    let message = message.unwrap_or_else(|| "comment=hello;user=guest".to_string()).into_bytes();
+    let suffix = suffix.unwrap_or_else(|| ";admin=true".to_string()).into_bytes();
+    let secret_lengths = parse_length_range(secret_lengths.as_deref().unwrap_or("0-64")).unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        process::exit(2);
+    });
 
+    // the secret is random, its length is somewhere in the guessed range
+    let span = (secret_lengths.end() - secret_lengths.start() + 1) as u64;
+    let mut oracle = length_extension::Oracle::new(random_bytes(secret_lengths.start() + random_below(span) as usize));
+    let tag = oracle.tag(&message);
+    println!("Message: {}\nTag: {}", String::from_utf8_lossy(&message), hex::encode(tag));
+
+    let result = length_extension::attack(&message, &tag, secret_lengths, &suffix, |forged, forged_tag| {
+        oracle.verify(forged, forged_tag)
+    });
+    match result {
+        Ok(forgery) => {
+            println!("Secret length: {} bytes ({} verifier queries)", forgery.secret_length, oracle.n_queries);
+            println!("Forged message: {}", hex::encode(&forgery.message));
+            println!("Forged tag: {}", hex::encode(forgery.tag));
+            println!("Verifier accepts the forged message: {}", oracle.verify(&forgery.message, &forgery.tag));
+        }
+        Err(error) => {
+            eprintln!("Attack failed: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/**
+ * Parses the value of --hash, SHA-256 by default
+ */
+fn hash_from_args(args: &[String]) -> (HashFunction, Vec<String>) {
+    let (name, args) = option_from_args(args, "--hash");
+    let hash = name.map_or(Ok(hash::DEFAULT_HASH), |x| HashFunction::from_name(&x)).unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        process::exit(2);
+    });
+    (hash, args)
+}
+
+/**
+ * Prints the HMAC of a file (or stdin) with the key given in hex
+ */
+fn run_hmac(args: &[String]) {
+    let (hash, args) = hash_from_args(args);
+    let (key, path) = match args.as_slice() {
+        [key] => (key, None),
+        [key, path] => (key, Some(path)),
+        _ => {
+            eprintln!("Expected a key in hex\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let key = hex::decode(key).unwrap_or_else(|_| {
+        eprintln!("Expected a key in hex\n{}", USAGE);
+        process::exit(2);
+    });
+
+    let mut message = Vec::new();
+    let result = match path {
+        Some(path) => File::open(path).and_then(|mut file| file.read_to_end(&mut message)),
+        None => io::stdin().read_to_end(&mut message),
+    };
+    if let Err(error) = result {
+        eprintln!("Unable to read the message: {}", error);
+        process::exit(1);
+    }
+    println!("HMAC-{}: {}", hash.name(), hex::encode(hmac::hmac(hash, &key, &message)));
+}
+
+/**
+ * Parses --tag-length, the full output of the hash by default
+ */
+fn tag_length_from_args(args: &[String], hash: HashFunction) -> (usize, Vec<String>) {
+    let (tag_length, args) = number_from_args(args, "--tag-length");
+    let tag_length = tag_length.unwrap_or(hash.output_length());
+    if tag_length == 0 || tag_length > hash.output_length() {
+        eprintln!("The tag length must be between 1 and {}\n{}", hash.output_length(), USAGE);
+        process::exit(2);
+    }
+    (tag_length, args)
+}
+
+/**
+ * Runs the tag verification service with a random key, with the early exit comparison unless --constant-time is given
+ */
+fn run_tag_server(args: &[String]) {
+    let (hash, args) = hash_from_args(args);
+    let (tag_length, args) = tag_length_from_args(&args, hash);
+    let (delay, args) = number_from_args(&args, "--delay-us");
+    let (port, mut args) = number_from_args(&args, "--port");
+    let comparison = match args.iter().position(|x| x == "--constant-time") {
+        Some(i) => {
+            args.remove(i);
+            hmac::Comparison::ConstantTime
+        }
+        None => hmac::Comparison::EarlyExit,
+    };
+    if !args.is_empty() {
+        eprintln!("Unexpected arguments {}\n{}", args.join(" "), USAGE);
+        process::exit(2);
+    }
+
+    let service = timing::Service {
+        key: random_bytes(32),
+        hash,
+        tag_length,
+        comparison,
+        byte_delay: std::time::Duration::from_micros(delay.unwrap_or(100)),
+    };
+    if let Err(error) = timing::serve(service, port.unwrap_or(timing::DEFAULT_PORT)) {
+        eprintln!("Unable to serve: {}", error);
+        process::exit(1);
+    }
+}
+
+/**
+ * Recovers a valid tag of the message from the response times of the verification service
+ * The hash only gives the default tag length, it has to be the one of the service
+ * Exits with status 1 when the recovered tag is not accepted
+ */
+fn run_timing_attack(args: &[String]) {
+    let (message, args) = option_from_args(args, "--message");
+    let (hash, args) = hash_from_args(&args);
+    let (tag_length, args) = tag_length_from_args(&args, hash);
+    let (samples, args) = number_from_args(&args, "--samples");
+    let (port, args) = number_from_args(&args, "--port");
+    if !args.is_empty() {
+        eprintln!("Unexpected arguments {}\n{}", args.join(" "), USAGE);
+        process::exit(2);
+    }
+    let message = message.unwrap_or_else(|| "user=guest;admin=true".to_string()).into_bytes();
+
+    let result = timing::Client::connect(port.unwrap_or(timing::DEFAULT_PORT)).and_then(|mut client| {
+        let tag = timing::recover_tag(&mut client, &message, tag_length, samples.unwrap_or(5).max(1), |prefix| {
+            eprintln!("{}", hex::encode(prefix));
+        })?;
+        Ok((tag, client.n_queries))
+    });
+
+    match result {
+        Ok((Some(tag), n_queries)) => println!("Tag of {} accepted after {} queries: {}",
+            String::from_utf8_lossy(&message), n_queries, hex::encode(tag)),
+        Ok((None, n_queries)) => {
+            eprintln!("Attack failed: no recovered tag was accepted after {} queries", n_queries);
+            process::exit(1);
+        }
+        Err(error) => {
+            eprintln!("Attack failed: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+fn random_bytes(n_bytes: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; n_bytes];
+    getrandom::getrandom(&mut bytes).expect("unable to read random bytes");
+    bytes
+}
+
+fn random_below(bound: u64) -> u64 {
+    let mut bytes = [0u8; 8];
+    getrandom::getrandom(&mut bytes).expect("unable to read random bytes");
+    u64::from_le_bytes(bytes) % bound
+}
+
+/**
+ * File or directory of a test in the temporary directory, removed when dropped
+ */
+#[cfg(test)]
+struct TempPath(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempPath {
+    fn new(name: &str) -> TempPath {
+        TempPath(env::temp_dir().join(format!("chunk-hashing-{}-{}", process::id(), name)))
+    }
+
+    fn path(&self) -> String {
+        self.0.display().to_string()
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = match self.0.is_dir() {
+            true => fs::remove_dir_all(&self.0),
+            false => fs::remove_file(&self.0),
+        };
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(|x| x.as_str()) {
+        None => run_hash(&[]),
+        Some("hash") => run_hash(&args[2..]),
+        Some("encode") => run_encode(&args[2..]),
+        Some("verify") => run_verify(&args[2..]),
+        Some("serve") => run_serve(&args[2..]),
+        Some("fetch") => run_fetch(&args[2..]),
+        Some("length-extension") => run_length_extension(&args[2..]),
+        Some("hmac") => run_hmac(&args[2..]),
+        Some("tag-server") => run_tag_server(&args[2..]),
+        Some("timing-attack") => run_timing_attack(&args[2..]),
+        Some("keygen") => run_keygen(&args[2..]),
+        Some("sign") => run_sign(&args[2..]),
+        Some("verify-manifest") => run_verify_manifest(&args[2..]),
+        Some("merkle-root") => run_merkle_root(&args[2..]),
+        Some("merkle-proof") => run_merkle_proof(&args[2..]),
+        Some("merkle-verify") => run_merkle_verify(&args[2..]),
+        Some(command) => {
+            eprintln!("Unknown command {}\n{}", command, USAGE);
+            process::exit(2);
+        }
+    }
 }
@@ -0,0 +1,117 @@
+use std::fs;
+use crate::hash::{HashFunction, Parameters, DEFAULT_HASH};
+
+/**
+ * How the chunks are bound to the trusted hash
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scheme {
+    // every block carries the hash of the next one, the trusted hash is H0 (`h0=` line)
+    Chain,
+    // binary tree over the chunks, the trusted hash is its root (`root=` line)
+    Merkle,
+}
+
+/**
+ * Small text file published next to the stream, one `name=value` pair per line
+ * H0 or the root is the only value the client has to trust, the rest describes the stream
+ * Manifests written before the hash was configurable have no hash line and use SHA-256
+ * The name of the file and an Ed25519 signature of all the other lines are optional
+ */
+pub struct Manifest {
+    pub scheme: Scheme,
+    pub trusted_hash: Vec<u8>,
+    pub chunk_length: usize,
+    pub hash: HashFunction,
+    pub payload_length: u64,
+    pub name: Option<String>,
+    pub signature: Option<Vec<u8>>,
+}
+
+/**
+ * Whether the name reads back unchanged from its manifest line, which is split at line breaks and trimmed
+ */
+pub fn is_valid_name(name: &str) -> bool {
+    !name.contains(['\n', '\r']) && name.trim() == name
+}
+
+impl Manifest {
+
+    /**
+     * Every line but the signature, in a fixed order, this is the signed text
+     */
+    pub fn fields_text(&self) -> String {
+        let hash_name = match self.scheme {
+            Scheme::Chain => "h0",
+            Scheme::Merkle => "root",
+        };
+        let mut text = format!("{}={}\nchunk_length={}\nhash={}\npayload_length={}\n",
+            hash_name, hex::encode(&self.trusted_hash), self.chunk_length, self.hash.name(), self.payload_length);
+        if let Some(name) = &self.name {
+            text += &format!("name={}\n", name);
+        }
+        text
+    }
+
+    pub fn to_text(&self) -> String {
+        match &self.signature {
+            Some(signature) => format!("{}signature={}\n", self.fields_text(), hex::encode(signature)),
+            None => self.fields_text(),
+        }
+    }
+
+    pub fn parameters(&self) -> Parameters {
+        Parameters { chunk_length: self.chunk_length, hash: self.hash }
+    }
+
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        let mut trusted_hash: Option<(Scheme, Vec<u8>)> = None;
+        let mut chunk_length: Option<usize> = None;
+        let mut hash = DEFAULT_HASH;
+        let mut payload_length: Option<u64> = None;
+        let mut file_name: Option<String> = None;
+        let mut signature: Option<Vec<u8>> = None;
+
+        for line in text.lines().map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            let (name, value) = line.split_once('=').ok_or(format!("invalid manifest line: {}", line))?;
+            match name {
+                "h0" | "root" if trusted_hash.is_some() => return Err("manifest has more than one h0 or root".to_string()),
+                "h0" => trusted_hash = Some((Scheme::Chain, hex::decode(value).map_err(|x| format!("invalid h0: {:?}", x))?)),
+                "root" => trusted_hash = Some((Scheme::Merkle, hex::decode(value).map_err(|x| format!("invalid root: {:?}", x))?)),
+                "chunk_length" => chunk_length = Some(value.parse().map_err(|x| format!("invalid chunk_length: {}", x))?),
+                "hash" => hash = HashFunction::from_name(value)?,
+                "payload_length" => payload_length = Some(value.parse().map_err(|x| format!("invalid payload_length: {}", x))?),
+                "name" => file_name = Some(value.to_string()),
+                "signature" => signature = Some(hex::decode(value).map_err(|x| format!("invalid signature: {:?}", x))?),
+                other => return Err(format!("unknown manifest field {}", other)),
+            }
+        }
+
+        let (scheme, trusted_hash) = trusted_hash.ok_or("manifest has no h0 or root")?;
+        let manifest = Manifest {
+            scheme,
+            trusted_hash,
+            chunk_length: chunk_length.ok_or("manifest has no chunk_length")?,
+            hash,
+            payload_length: payload_length.ok_or("manifest has no payload_length")?,
+            name: file_name,
+            signature,
+        };
+        if manifest.chunk_length == 0 {
+            return Err("chunk_length must be positive".to_string());
+        }
+        if manifest.trusted_hash.len() != hash.output_length() {
+            return Err(format!("trusted hash is not a {} hash", hash.name()));
+        }
+        Ok(manifest)
+    }
+
+    pub fn read(path: &str) -> Result<Manifest, String> {
+        let text = fs::read_to_string(path).map_err(|x| format!("unable to read {}: {}", path, x))?;
+        Manifest::parse(&text).map_err(|x| format!("{}: {}", path, x))
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|x| format!("unable to write {}: {}", path, x))
+    }
+}
@@ -0,0 +1,252 @@
+use std::io::{self, Read};
+use crate::hash::{HashFunction, Parameters};
+
+// prefixes of the hashed data, so that a leaf can never be taken for a node (as in RFC 6962)
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/**
+ * Binary hash tree over the chunks of the input, any chunk can be verified alone against the root
+ * The tree is the one of RFC 6962: a node without a sibling is promoted to the next level as is
+ * levels[0] holds the hashes of the leaves, the last level holds the root
+ */
+pub struct MerkleTree {
+    hash: HashFunction,
+    levels: Vec<Vec<Vec<u8>>>,
+}
+
+fn hash_leaf(hash: HashFunction, chunk: &[u8]) -> Vec<u8> {
+    let mut data = vec![LEAF_PREFIX];
+    data.extend(chunk);
+    hash.hash(&data)
+}
+
+fn hash_node(hash: HashFunction, left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut data = vec![NODE_PREFIX];
+    data.extend(left);
+    data.extend(right);
+    hash.hash(&data)
+}
+
+impl MerkleTree {
+
+    /**
+     * Reads the input once from the start, only the hashes are kept in memory
+     */
+    pub fn build<R: Read>(reader: &mut R, parameters: &Parameters) -> io::Result<MerkleTree> {
+        let mut leaves: Vec<Vec<u8>> = Vec::new();
+        let mut chunk: Vec<u8> = Vec::with_capacity(parameters.chunk_length);
+        loop {
+            chunk.clear();
+            reader.by_ref().take(parameters.chunk_length as u64).read_to_end(&mut chunk)?;
+            if chunk.is_empty() {
+                break;
+            }
+            leaves.push(hash_leaf(parameters.hash, &chunk));
+        }
+        Ok(MerkleTree::from_leaves(parameters.hash, leaves))
+    }
+
+    fn from_leaves(hash: HashFunction, leaves: Vec<Vec<u8>>) -> MerkleTree {
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap();
+            let next = level.chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(hash, left, right),
+                    [single] => single.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        MerkleTree { hash, levels }
+    }
+
+    pub fn leaf_count(&self) -> u64 {
+        self.levels[0].len() as u64
+    }
+
+    /**
+     * Root of the tree, the hash of the empty string for an empty input
+     */
+    pub fn root(&self) -> Vec<u8> {
+        match self.levels.last().unwrap().first() {
+            Some(root) => root.clone(),
+            None => self.hash.hash(&[]),
+        }
+    }
+
+    /**
+     * Inclusion proof of the chunk at index: the siblings on the path from its leaf to the root
+     */
+    pub fn proof(&self, index: u64) -> Result<Vec<Vec<u8>>, String> {
+        if index >= self.leaf_count() {
+            return Err(format!("chunk {} is out of range, the input has {} chunks", index, self.leaf_count()));
+        }
+
+        let mut proof = Vec::new();
+        let mut position = index as usize;
+        for level in &self.levels[..self.levels.len() - 1] {
+            // a promoted node has no sibling on this level
+            if let Some(sibling) = level.get(position ^ 1) {
+                proof.push(sibling.clone());
+            }
+            position /= 2;
+        }
+        Ok(proof)
+    }
+}
+
+/**
+ * Verifies that chunk is the chunk at index of a tree with leaf_count leaves and the given root
+ * The shape of the path is derived from index and leaf_count (RFC 9162, section 2.1.3.2),
+ * so a proof for one position cannot be replayed for another one
+ */
+pub fn verify_proof(
+    hash: HashFunction,
+    root: &[u8],
+    leaf_count: u64,
+    index: u64,
+    chunk: &[u8],
+    proof: &[Vec<u8>]
+) -> Result<(), String> {
+    if index >= leaf_count {
+        return Err(format!("chunk {} is out of range, the tree has {} chunks", index, leaf_count));
+    }
+
+    let mut position = index;
+    let mut last_position = leaf_count - 1;
+    let mut computed = hash_leaf(hash, chunk);
+    for sibling in proof {
+        if last_position == 0 {
+            return Err("proof is longer than the path to the root".to_string());
+        }
+        if !position.is_multiple_of(2) || position == last_position {
+            computed = hash_node(hash, sibling, &computed);
+            // skip the levels where the node is promoted
+            while position.is_multiple_of(2) && position != 0 {
+                position >>= 1;
+                last_position >>= 1;
+            }
+        } else {
+            computed = hash_node(hash, &computed, sibling);
+        }
+        position >>= 1;
+        last_position >>= 1;
+    }
+
+    if last_position != 0 {
+        return Err("proof is shorter than the path to the root".to_string());
+    }
+    match computed == root {
+        true => Ok(()),
+        false => Err(format!("chunk {} does not match the root", index)),
+    }
+}
+
+/**
+ * Proof file: one sibling hash in hex per line, from the leaf up to the root
+ */
+pub fn proof_to_text(proof: &[Vec<u8>]) -> String {
+    proof.iter().map(|x| hex::encode(x) + "\n").collect()
+}
+
+pub fn parse_proof(text: &str, hash: HashFunction) -> Result<Vec<Vec<u8>>, String> {
+    text.lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|line| match hex::decode(line) {
+            Ok(sibling) if sibling.len() == hash.output_length() => Ok(sibling),
+            _ => Err(format!("invalid proof line: {}", line)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::random_bytes;
+    use super::*;
+
+    const PARAMETERS: Parameters = Parameters { chunk_length: 16, hash: HashFunction::Sha256 };
+
+    // leaves and roots of the certificate-transparency test suite, roots of the trees over the first 1 to 8 leaves
+    const RFC6962_LEAVES: [&str; 8] = [
+        "", "00", "10", "2021", "3031", "40414243", "5051525354555657", "606162636465666768696a6b6c6d6e6f",
+    ];
+    const RFC6962_ROOTS: [&str; 8] = [
+        "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+        "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+        "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+        "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+        "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+        "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+        "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+    ];
+
+    #[test]
+    fn rfc6962_roots() {
+        let leaves: Vec<Vec<u8>> = RFC6962_LEAVES.iter().map(|x| hex::decode(x).unwrap()).collect();
+        for (n_leaves, root) in RFC6962_ROOTS.iter().enumerate().map(|(i, root)| (i + 1, root)) {
+            let hashes = leaves[..n_leaves].iter().map(|x| hash_leaf(HashFunction::Sha256, x)).collect();
+            assert_eq!(hex::encode(MerkleTree::from_leaves(HashFunction::Sha256, hashes).root()), *root);
+        }
+    }
+
+    #[test]
+    fn proofs() {
+        for n_leaves in 1..=20u64 {
+            // the last chunk is a short one
+            let input = random_bytes(n_leaves as usize * PARAMETERS.chunk_length - 5);
+            let chunks: Vec<&[u8]> = input.chunks(PARAMETERS.chunk_length).collect();
+            let tree = MerkleTree::build(&mut Cursor::new(&input), &PARAMETERS).unwrap();
+            assert_eq!(tree.leaf_count(), n_leaves);
+            let root = tree.root();
+
+            for index in 0..n_leaves {
+                let chunk = chunks[index as usize];
+                let proof = tree.proof(index).unwrap();
+                assert_eq!(verify_proof(PARAMETERS.hash, &root, n_leaves, index, chunk, &proof), Ok(()));
+
+                // wrong chunk
+                let mut other_chunk = chunk.to_vec();
+                other_chunk[0] ^= 1;
+                assert!(verify_proof(PARAMETERS.hash, &root, n_leaves, index, &other_chunk, &proof).is_err());
+
+                // the proof of a chunk is not valid at any other index
+                for other_index in (0..n_leaves + 1).filter(|x| *x != index) {
+                    assert!(verify_proof(PARAMETERS.hash, &root, n_leaves, other_index, chunk, &proof).is_err());
+                }
+
+                // proof too long or too short
+                let mut long_proof = proof.clone();
+                long_proof.push(root.clone());
+                assert!(verify_proof(PARAMETERS.hash, &root, n_leaves, index, chunk, &long_proof).is_err());
+                if !proof.is_empty() {
+                    let short_proof = &proof[..proof.len() - 1];
+                    assert!(verify_proof(PARAMETERS.hash, &root, n_leaves, index, chunk, short_proof).is_err());
+                }
+            }
+            assert!(tree.proof(n_leaves).is_err());
+        }
+    }
+
+    #[test]
+    fn empty_tree() {
+        let tree = MerkleTree::build(&mut Cursor::new(&[]), &PARAMETERS).unwrap();
+        assert_eq!(tree.leaf_count(), 0);
+        assert_eq!(tree.root(), PARAMETERS.hash.hash(&[]));
+        assert!(tree.proof(0).is_err());
+        assert!(verify_proof(PARAMETERS.hash, &tree.root(), 0, 0, &[], &[]).is_err());
+    }
+
+    #[test]
+    fn proof_text() {
+        let tree = MerkleTree::build(&mut Cursor::new(random_bytes(100)), &PARAMETERS).unwrap();
+        let proof = tree.proof(3).unwrap();
+        assert_eq!(parse_proof(&proof_to_text(&proof), PARAMETERS.hash), Ok(proof));
+        assert!(parse_proof("00\n", PARAMETERS.hash).is_err());
+    }
+}
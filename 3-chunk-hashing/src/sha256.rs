@@ -0,0 +1,238 @@
+use std::convert::TryInto;
+
+// SHA-256 written from FIPS 180-4, with the internal state exposed
+
+pub const BLOCK_LENGTH: usize = 64;
+pub const OUTPUT_LENGTH: usize = 32;
+
+// first 32 bits of the fractional parts of the square roots of the first 8 primes (section 5.3.3)
+pub const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// first 32 bits of the fractional parts of the cube roots of the first 64 primes (section 4.2.2)
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/**
+ * Compression function: updates the state with one 64 byte block (section 6.2.2)
+ */
+pub fn compress(state: &mut [u32; 8], block: &[u8; BLOCK_LENGTH]) {
+    // message schedule
+    let mut w = [0u32; 64];
+    for (t, word) in block.chunks_exact(4).enumerate() {
+        w[t] = u32::from_be_bytes(word.try_into().unwrap());
+    }
+    for t in 16..64 {
+        let sigma0 = w[t - 15].rotate_right(7) ^ w[t - 15].rotate_right(18) ^ (w[t - 15] >> 3);
+        let sigma1 = w[t - 2].rotate_right(17) ^ w[t - 2].rotate_right(19) ^ (w[t - 2] >> 10);
+        w[t] = w[t - 16].wrapping_add(sigma0).wrapping_add(w[t - 7]).wrapping_add(sigma1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for t in 0..64 {
+        let big_sigma1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let choose = (e & f) ^ (!e & g);
+        let t1 = h.wrapping_add(big_sigma1).wrapping_add(choose).wrapping_add(K[t]).wrapping_add(w[t]);
+        let big_sigma0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let majority = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = big_sigma0.wrapping_add(majority);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+/**
+ * Padding appended to a message of message_length bytes (section 5.1.1):
+ * 0x80, zeros up to 56 bytes modulo 64, then the length in bits as a big endian u64
+ */
+pub fn padding(message_length: u64) -> Vec<u8> {
+    let n_zeros = (BLOCK_LENGTH * 2 - 9 - (message_length as usize % BLOCK_LENGTH)) % BLOCK_LENGTH;
+    let mut padding = vec![0x80];
+    padding.extend(vec![0u8; n_zeros]);
+    padding.extend(&message_length.wrapping_mul(8).to_be_bytes());
+    padding
+}
+
+/**
+ * Incremental hasher, `update` can be called with pieces of any length
+ */
+#[derive(Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    // number of bytes hashed so far, including the ones waiting in the buffer
+    length: u64,
+    buffer: [u8; BLOCK_LENGTH],
+    buffer_length: usize,
+}
+
+impl Default for Sha256 {
+    fn default() -> Sha256 {
+        Sha256::new()
+    }
+}
+
+impl Sha256 {
+
+    pub fn new() -> Sha256 {
+        Sha256::from_state(INITIAL_STATE, 0)
+    }
+
+    /**
+     * Continues from a state reached after length bytes, which must be a multiple of the block length
+     * (e.g. the state read back from a digest, whose message ended with its padding)
+     */
+    pub fn from_state(state: [u32; 8], length: u64) -> Sha256 {
+        assert!(length.is_multiple_of(BLOCK_LENGTH as u64), "the state is only known at block boundaries");
+        Sha256 { state, length, buffer: [0u8; BLOCK_LENGTH], buffer_length: 0 }
+    }
+
+    /**
+     * Chaining value after the last complete block and the number of bytes it covers
+     */
+    // exported for the users of the module, only the tests use it in this program
+    #[allow(dead_code)]
+    pub fn state(&self) -> ([u32; 8], u64) {
+        (self.state, self.length - self.buffer_length as u64)
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+
+        // complete the buffered block first
+        if self.buffer_length > 0 {
+            let n = data.len().min(BLOCK_LENGTH - self.buffer_length);
+            self.buffer[self.buffer_length..self.buffer_length + n].copy_from_slice(&data[..n]);
+            self.buffer_length += n;
+            data = &data[n..];
+            if self.buffer_length < BLOCK_LENGTH {
+                return;
+            }
+            compress(&mut self.state, &self.buffer);
+            self.buffer_length = 0;
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_LENGTH);
+        for block in &mut blocks {
+            compress(&mut self.state, block.try_into().unwrap());
+        }
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffer_length = rest.len();
+    }
+
+    pub fn finalize(mut self) -> [u8; OUTPUT_LENGTH] {
+        let padding = padding(self.length);
+        self.update(&padding);
+        debug_assert_eq!(self.buffer_length, 0);
+        state_to_digest(&self.state)
+    }
+
+    // used for the chunk hashes with the scratch-sha256 feature only
+    #[allow(dead_code)]
+    pub fn digest(data: &[u8]) -> [u8; OUTPUT_LENGTH] {
+        let mut hasher = Sha256::new();
+        hasher.update(data);
+        hasher.finalize()
+    }
+}
+
+pub fn state_to_digest(state: &[u32; 8]) -> [u8; OUTPUT_LENGTH] {
+    let mut digest = [0u8; OUTPUT_LENGTH];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/**
+ * The digest is the final state, so it can be read back to continue hashing
+ */
+pub fn digest_to_state(digest: &[u8; OUTPUT_LENGTH]) -> [u32; 8] {
+    let mut state = [0u32; 8];
+    for (word, bytes) in state.iter_mut().zip(digest.chunks_exact(4)) {
+        *word = u32::from_be_bytes(bytes.try_into().unwrap());
+    }
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Digest;
+    use crate::{random_below, random_bytes};
+    use super::*;
+
+    // FIPS 180-4 examples (the NIST "Example Algorithms" document) and the empty message
+    const VECTORS: [(&str, &str); 4] = [
+        ("", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+        ("abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        ("abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"),
+        ("abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+            "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1"),
+    ];
+
+    #[test]
+    fn fips180_vectors() {
+        for (message, expected) in VECTORS {
+            assert_eq!(hex::encode(Sha256::digest(message.as_bytes())), expected, "{:?}", message);
+        }
+
+        let mut hasher = Sha256::new();
+        for _ in 0..1000 {
+            hasher.update(&[b'a'; 1000]);
+        }
+        assert_eq!(hex::encode(hasher.finalize()), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+    }
+
+    #[test]
+    fn random_pieces() {
+        for _ in 0..500 {
+            let message = random_bytes(random_below(1000) as usize);
+            let expected = sha2::Sha256::digest(&message);
+
+            let mut hasher = Sha256::new();
+            let mut rest = message.as_slice();
+            while !rest.is_empty() {
+                let (piece, next) = rest.split_at(1 + random_below(rest.len() as u64) as usize);
+                hasher.update(piece);
+                rest = next;
+            }
+
+            // a new hasher started from the exported state has to reach the same digest
+            let (state, length) = hasher.state();
+            let mut resumed = Sha256::from_state(state, length);
+            resumed.update(&message[length as usize..]);
+            assert_eq!(resumed.finalize()[..], expected[..]);
+
+            // the digest is the state after the padded message, hashing can go on from it
+            let digest = hasher.finalize();
+            assert_eq!(digest[..], expected[..]);
+            let mut padded = message.clone();
+            padded.extend(padding(message.len() as u64));
+            let mut continued = Sha256::from_state(digest_to_state(&digest), padded.len() as u64);
+            continued.update(&message);
+            padded.extend(&message);
+            assert_eq!(continued.finalize()[..], sha2::Sha256::digest(&padded)[..]);
+        }
+    }
+}
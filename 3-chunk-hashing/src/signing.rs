@@ -0,0 +1,170 @@
+use std::convert::TryInto;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use crate::manifest::{self, Manifest};
+
+// prepended to the signed lines, so a manifest signature cannot be taken for a signature of anything else
+const SIGNATURE_CONTEXT: &[u8] = b"chunk-hashing manifest v1\n";
+
+/**
+ * Key files hold the 32 bytes of the key in hex on one line
+ */
+fn read_key(path: &str) -> Result<[u8; 32], String> {
+    let text = fs::read_to_string(path).map_err(|x| format!("unable to read {}: {}", path, x))?;
+    hex::decode(text.trim()).ok()
+        .and_then(|x| x.try_into().ok())
+        .ok_or(format!("{} does not hold a 32 byte key in hex", path))
+}
+
+/**
+ * Creates a key file, an existing file is never overwritten
+ * A secret key file is created readable and writable by its owner only
+ */
+fn write_key(path: &str, key: &[u8], secret: bool) -> Result<(), String> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(if secret { 0o600 } else { 0o644 });
+
+    let mut file = options.open(path).map_err(|x| match x.kind() {
+        ErrorKind::AlreadyExists => format!("{} already exists, refusing to overwrite it", path),
+        _ => format!("unable to create {}: {}", path, x),
+    })?;
+    file.write_all((hex::encode(key) + "\n").as_bytes()).map_err(|x| format!("unable to write {}: {}", path, x))
+}
+
+/**
+ * Generates a key pair from the operating system's random generator
+ * Fails without writing anything when one of the key files exists
+ * Returns the public key
+ */
+pub fn generate_keys(secret_key_path: &str, public_key_path: &str) -> Result<VerifyingKey, String> {
+    if let Some(path) = [secret_key_path, public_key_path].iter().find(|x| Path::new(x).exists()) {
+        return Err(format!("{} already exists, refusing to overwrite it", path));
+    }
+
+    let mut secret_key = [0u8; 32];
+    getrandom::getrandom(&mut secret_key).map_err(|x| format!("unable to read random bytes: {}", x))?;
+    let signing_key = SigningKey::from_bytes(&secret_key);
+
+    write_key(secret_key_path, &secret_key, true)?;
+    write_key(public_key_path, signing_key.verifying_key().as_bytes(), false)?;
+    Ok(signing_key.verifying_key())
+}
+
+fn signed_message(manifest: &Manifest) -> Vec<u8> {
+    let mut message = SIGNATURE_CONTEXT.to_vec();
+    message.extend(manifest.fields_text().as_bytes());
+    message
+}
+
+/**
+ * Signs every field of the manifest, replacing an earlier signature
+ * A name which would not read back the same from the manifest line is rejected, the signature would not verify
+ */
+pub fn sign(manifest: &mut Manifest, secret_key_path: &str) -> Result<(), String> {
+    if let Some(name) = manifest.name.as_ref().filter(|x| !manifest::is_valid_name(x)) {
+        return Err(format!("name {:?} contains a line break or surrounding spaces", name));
+    }
+    let signing_key = SigningKey::from_bytes(&read_key(secret_key_path)?);
+    manifest.signature = Some(signing_key.sign(&signed_message(manifest)).to_bytes().to_vec());
+    Ok(())
+}
+
+/**
+ * Checks the signature of the manifest against the public key, an unsigned manifest is rejected
+ */
+pub fn verify(manifest: &Manifest, public_key_path: &str) -> Result<(), String> {
+    let public_key = VerifyingKey::from_bytes(&read_key(public_key_path)?)
+        .map_err(|_| format!("{} is not a valid Ed25519 public key", public_key_path))?;
+    let signature = manifest.signature.as_ref().ok_or("manifest is not signed")?;
+    let signature = Signature::from_slice(signature).map_err(|_| "signature is not 64 bytes long")?;
+
+    // strict verification rejects malleable signatures and small order keys
+    public_key.verify_strict(&signed_message(manifest), &signature)
+        .map_err(|_| "invalid manifest signature".to_string())
+}
+
+/**
+ * Reads a manifest and, when a public key is given, checks its signature
+ */
+pub fn read_manifest(path: &str, public_key_path: Option<&str>) -> Result<Manifest, String> {
+    let manifest = Manifest::read(path)?;
+    if let Some(public_key_path) = public_key_path {
+        verify(&manifest, public_key_path).map_err(|x| format!("{}: {}", path, x))?;
+    }
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TempPath;
+    use crate::hash::HashFunction;
+    use crate::manifest::Scheme;
+    use super::*;
+
+    /**
+     * Key paths in a new directory of the test, removed with the returned guard
+     */
+    fn key_paths(test: &str) -> (TempPath, String, String) {
+        let directory = TempPath::new(test);
+        let _ = fs::remove_dir_all(&directory.0);
+        fs::create_dir_all(&directory.0).unwrap();
+        let path = |x: &str| directory.0.join(x).to_str().unwrap().to_string();
+        let (secret_key_path, public_key_path) = (path("secret.key"), path("public.key"));
+        (directory, secret_key_path, public_key_path)
+    }
+
+    fn manifest(name: &str) -> Manifest {
+        Manifest {
+            scheme: Scheme::Chain,
+            trusted_hash: vec![0u8; 32],
+            chunk_length: 1024,
+            hash: HashFunction::Sha256,
+            payload_length: 5000,
+            name: Some(name.to_string()),
+            signature: None,
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn secret_key_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let (_directory, secret_key_path, public_key_path) = key_paths("mode");
+        generate_keys(&secret_key_path, &public_key_path).unwrap();
+        assert_eq!(fs::metadata(&secret_key_path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn keys_are_not_overwritten() {
+        let (_directory, secret_key_path, public_key_path) = key_paths("overwrite");
+        generate_keys(&secret_key_path, &public_key_path).unwrap();
+        let secret_key = fs::read(&secret_key_path).unwrap();
+        assert!(generate_keys(&secret_key_path, &public_key_path).is_err());
+        assert_eq!(fs::read(&secret_key_path).unwrap(), secret_key);
+
+        // nothing is written when only the public key exists
+        fs::remove_file(&secret_key_path).unwrap();
+        assert!(generate_keys(&secret_key_path, &public_key_path).is_err());
+        assert!(!Path::new(&secret_key_path).exists());
+    }
+
+    #[test]
+    fn names() {
+        let (_directory, secret_key_path, public_key_path) = key_paths("names");
+        generate_keys(&secret_key_path, &public_key_path).unwrap();
+
+        let mut signed = manifest("input1.mp4");
+        sign(&mut signed, &secret_key_path).unwrap();
+        assert!(verify(&signed, &public_key_path).is_ok());
+
+        for name in ["input1.mp4\nh0=00", "input1.mp4\r", " input1.mp4", "input1.mp4 "] {
+            assert!(sign(&mut manifest(name), &secret_key_path).is_err(), "{:?} was signed", name);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use crate::hash::{HashFunction, Parameters};
+use crate::verify::{self, ChainState, VerifyError};
+
+/**
+ * Progress of a resumable verification, one `name=value` pair per line like the manifest
+ * H0 and the parameters identify the stream, so a state file cannot be resumed against another one
+ */
+pub struct StateFile {
+    pub h0: Vec<u8>,
+    pub parameters: Parameters,
+    pub state: ChainState,
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid {}: {}", name, value))
+}
+
+fn parse_hex(name: &str, value: &str) -> Result<Vec<u8>, String> {
+    hex::decode(value).map_err(|x| format!("invalid {}: {:?}", name, x))
+}
+
+impl StateFile {
+
+    pub fn to_text(&self) -> String {
+        format!("h0={}\nchunk_length={}\nhash={}\nblock={}\noffset={}\npayload_length={}\nexpected_hash={}\n",
+            hex::encode(&self.h0), self.parameters.chunk_length, self.parameters.hash.name(),
+            self.state.block, self.state.offset, self.state.payload_length, hex::encode(&self.state.expected_hash))
+    }
+
+    pub fn parse(text: &str) -> Result<StateFile, String> {
+        let mut fields: Vec<(&str, &str)> = Vec::new();
+        for line in text.lines().map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            fields.push(line.split_once('=').ok_or(format!("invalid state line: {}", line))?);
+        }
+        let field = |name: &str| fields.iter()
+            .find(|(x, _)| *x == name)
+            .map(|(_, value)| *value)
+            .ok_or(format!("state has no {}", name));
+
+        let hash = HashFunction::from_name(field("hash")?)?;
+        let state_file = StateFile {
+            h0: parse_hex("h0", field("h0")?)?,
+            parameters: Parameters { chunk_length: parse_number("chunk_length", field("chunk_length")?)?, hash },
+            state: ChainState {
+                block: parse_number("block", field("block")?)?,
+                offset: parse_number("offset", field("offset")?)?,
+                payload_length: parse_number("payload_length", field("payload_length")?)?,
+                expected_hash: parse_hex("expected_hash", field("expected_hash")?)?,
+            },
+        };
+
+        if state_file.parameters.chunk_length == 0 {
+            return Err("chunk_length must be positive".to_string());
+        }
+        if state_file.h0.len() != hash.output_length()
+            || !(state_file.state.is_complete() || state_file.state.expected_hash.len() == hash.output_length()) {
+            return Err(format!("state hashes are not {} hashes", hash.name()));
+        }
+        Ok(state_file)
+    }
+
+    /**
+     * Returns None when there is no state file yet
+     */
+    pub fn read(path: &str) -> Result<Option<StateFile>, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => StateFile::parse(&text).map(Some).map_err(|x| format!("{}: {}", path, x)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(format!("unable to read {}: {}", path, error)),
+        }
+    }
+
+    /**
+     * Writes a temporary file next to path and renames it, so an interruption leaves either the old or the new state
+     */
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        let temporary_path = format!("{}.tmp", path);
+        let mut file = File::create(&temporary_path)?;
+        file.write_all(self.to_text().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temporary_path, path)
+    }
+}
+
+/**
+ * Verifies a stream into the file at output_path, persisting the state to state_path after every block
+ * When the state file exists the verification continues from it: the output is cut back to the verified payload
+ * and open_stream is called with the offset of the next block, so earlier data is neither read nor checked again
+ * The state file is removed once the last block is verified
+ * Returns the final state
+ */
+pub fn resume<R: Read, F: FnOnce(u64) -> io::Result<R>>(
+    h0: &[u8],
+    parameters: &Parameters,
+    state_path: &str,
+    output_path: &str,
+    open_stream: F
+) -> Result<ChainState, String> {
+    let state = match StateFile::read(state_path)? {
+        Some(saved) if saved.h0 != h0 || saved.parameters != *parameters =>
+            return Err(format!("{} belongs to another stream", state_path)),
+        Some(saved) => saved.state,
+        None => ChainState::start(h0),
+    };
+
+    let mut output = OpenOptions::new().write(true).create(true).truncate(false).open(output_path)
+        .map_err(|x| format!("unable to write {}: {}", output_path, x))?;
+    let output_length = output.metadata().map_err(|x| format!("unable to read {}: {}", output_path, x))?.len();
+    if output_length < state.payload_length {
+        return Err(format!("{} is shorter than the {} verified bytes of {}", output_path, state.payload_length, state_path));
+    }
+
+    // drop the payload written after the last saved state
+    output.set_len(state.payload_length)
+        .and_then(|_| output.seek(SeekFrom::End(0)))
+        .map_err(|x| format!("unable to write {}: {}", output_path, x))?;
+
+    let save = |state: &ChainState| {
+        // the payload has to be on disk before the state which counts it
+        output.sync_data()?;
+        StateFile { h0: h0.to_vec(), parameters: *parameters, state: state.clone() }.write(state_path)
+    };
+    let state = open_stream(state.offset)
+        .map_err(VerifyError::from)
+        .and_then(|stream| verify::verify_from(state, parameters, stream, &output, save))
+        .map_err(|x| format!("Verification failed: {}", x))?;
+
+    fs::remove_file(state_path).map_err(|x| format!("unable to remove {}: {}", state_path, x))?;
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encode::write_augmented;
+    use crate::{random_below, random_bytes, TempPath};
+    use super::*;
+
+    const PARAMETERS: Parameters = Parameters { chunk_length: 100, hash: HashFunction::Sha256 };
+
+    /**
+     * Verifies the payload through the state and output files, the stream ending at a random byte every time
+     * like a killed download and the output getting unsaved bytes like a verifier killed before saving its state
+     * Returns the output and the number of interruptions
+     */
+    fn interrupted_verification(payload: &[u8], state_path: &str, output_path: &str) -> (Vec<u8>, usize) {
+        let mut stream = Vec::new();
+        let (h0, _) = write_augmented(&mut io::Cursor::new(payload), &mut stream, &PARAMETERS).unwrap();
+        let _ = fs::remove_file(state_path);
+        fs::write(output_path, b"").unwrap();
+
+        let mut n_interruptions = 0;
+        loop {
+            // a kill between writing a block and saving the state leaves payload which the state does not count
+            let mut output = OpenOptions::new().append(true).open(output_path).unwrap();
+            output.write_all(&random_bytes(random_below(PARAMETERS.chunk_length as u64 + 1) as usize)).unwrap();
+
+            let result = resume(&h0, &PARAMETERS, state_path, output_path, |offset| {
+                // the stream ends at a random byte after the resumed block, or is complete
+                let remaining = stream.len() as u64 - offset;
+                let cut = (offset + random_below(remaining + PARAMETERS.block_length() as u64)).min(stream.len() as u64);
+                let mut cursor = io::Cursor::new(&stream[..cut as usize]);
+                cursor.set_position(offset);
+                Ok(cursor)
+            });
+            match result {
+                Err(error) if error.starts_with("Verification failed") => n_interruptions += 1,
+                Err(error) => panic!("{}", error),
+                Ok(_) => break,
+            }
+        }
+        assert!(!std::path::Path::new(state_path).exists());
+        (fs::read(output_path).unwrap(), n_interruptions)
+    }
+
+    #[test]
+    fn interrupted_verifications() {
+        let (state_file, output_file) = (TempPath::new("interrupted.state"), TempPath::new("interrupted.out"));
+        let (state_path, output_path) = (state_file.path(), output_file.path());
+
+        let mut n_interruptions = 0;
+        for _ in 0..50 {
+            let payload = random_bytes(1 + random_below(20 * PARAMETERS.chunk_length as u64) as usize);
+            let (output, n) = interrupted_verification(&payload, &state_path, &output_path);
+            assert!(output == payload);
+            n_interruptions += n;
+        }
+        assert!(n_interruptions > 0);
+    }
+
+    #[test]
+    fn state_of_another_stream() {
+        let (state_file, output_file) = (TempPath::new("other.state"), TempPath::new("other.out"));
+        let (state_path, output_path) = (state_file.path(), output_file.path());
+
+        let saved = StateFile { h0: vec![1u8; 32], parameters: PARAMETERS, state: ChainState::start(&[1u8; 32]) };
+        saved.write(&state_path).unwrap();
+        let result = resume(&[2u8; 32], &PARAMETERS, &state_path, &output_path, |_| Ok(io::empty()));
+        assert!(matches!(result, Err(error) if error.contains("another stream")));
+
+        assert_eq!(StateFile::parse(&saved.to_text()).unwrap().to_text(), saved.to_text());
+    }
+}
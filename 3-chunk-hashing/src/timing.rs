@@ -0,0 +1,258 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::hash::HashFunction;
+use crate::hmac::{self, Comparison};
+
+// line protocol of the tag verification service, one request at a time on a connection:
+// the client sends `VERIFY <message hex> <tag hex>\n`, the service answers `OK\n`, `FAIL\n` or `ERR <reason>\n`
+pub const DEFAULT_PORT: u16 = 7879;
+
+/**
+ * Settings of the verification service, the key never leaves it
+ */
+#[derive(Clone)]
+pub struct Service {
+    pub key: Vec<u8>,
+    pub hash: HashFunction,
+    // the tag is the HMAC truncated to tag_length bytes
+    pub tag_length: usize,
+    pub comparison: Comparison,
+    pub byte_delay: Duration,
+}
+
+impl Service {
+
+    fn tag(&self, message: &[u8]) -> Vec<u8> {
+        let mut tag = hmac::hmac(self.hash, &self.key, message);
+        tag.truncate(self.tag_length);
+        tag
+    }
+
+    fn answer(&self, line: &str) -> String {
+        let request = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["VERIFY", message, tag] => hex::decode(message).ok().zip(hex::decode(tag).ok()),
+            _ => None,
+        };
+        match request {
+            Some((message, tag)) if hmac::compare(self.comparison, &tag, &self.tag(&message), self.byte_delay) => "OK\n".to_string(),
+            Some(_) => "FAIL\n".to_string(),
+            None => format!("ERR invalid request {}\n", line.trim()),
+        }
+    }
+}
+
+/**
+ * Verifies tags on localhost, every connection is handled by its own thread
+ */
+pub fn serve(service: Service, port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    eprintln!("Verifying {}-byte HMAC-{} tags on {}", service.tag_length, service.hash.name(), listener.local_addr()?);
+    accept(listener, service)
+}
+
+fn accept(listener: TcpListener, service: Service) -> io::Result<()> {
+    for connection in listener.incoming() {
+        let connection = connection?;
+        let service = service.clone();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(connection.try_clone()?);
+            let mut writer = connection;
+            let mut line = String::new();
+            while reader.read_line(&mut line)? > 0 {
+                writer.write_all(service.answer(&line).as_bytes())?;
+                line.clear();
+            }
+            Ok::<(), io::Error>(())
+        });
+    }
+    Ok(())
+}
+
+/**
+ * Connection to the verification service, which measures how long every answer takes
+ */
+pub struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    pub n_queries: usize,
+}
+
+impl Client {
+
+    pub fn connect(port: u16) -> io::Result<Client> {
+        let connection = TcpStream::connect((Ipv4Addr::LOCALHOST, port))?;
+        connection.set_nodelay(true)?;
+        Ok(Client { reader: BufReader::new(connection.try_clone()?), writer: connection, n_queries: 0 })
+    }
+
+    /**
+     * Returns whether the tag was accepted and the time from the request to the answer
+     */
+    pub fn verify(&mut self, message: &[u8], tag: &[u8]) -> io::Result<(bool, Duration)> {
+        let request = format!("VERIFY {} {}\n", hex::encode(message), hex::encode(tag));
+        let mut answer = String::new();
+
+        let start = Instant::now();
+        self.writer.write_all(request.as_bytes())?;
+        self.reader.read_line(&mut answer)?;
+        let elapsed = start.elapsed();
+
+        self.n_queries += 1;
+        match answer.trim() {
+            "OK" => Ok((true, elapsed)),
+            "FAIL" => Ok((false, elapsed)),
+            other => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected answer: {}", other))),
+        }
+    }
+}
+
+/**
+ * Median time of samples requests with the tag, the median ignores the requests slowed down by the scheduler
+ */
+fn median_time(client: &mut Client, message: &[u8], tag: &[u8], samples: usize) -> io::Result<Duration> {
+    let mut times = Vec::with_capacity(samples);
+    for _ in 0..samples {
+        times.push(client.verify(message, tag)?.1);
+    }
+    times.sort();
+    Ok(times[times.len() / 2])
+}
+
+// values measured again with more samples after the first pass over the 256 values
+const N_FINALISTS: usize = 8;
+
+/**
+ * Value of tag[position] which takes the longest to be rejected, that is the one the early exit comparison accepted
+ * The slowest values of a first pass are measured again with four times the samples, so that a single lucky outlier is not taken
+ */
+fn slowest_byte(client: &mut Client, message: &[u8], tag: &mut [u8], position: usize, samples: usize) -> io::Result<u8> {
+    let mut times = Vec::with_capacity(256);
+    for value in 0..=255u8 {
+        tag[position] = value;
+        times.push((median_time(client, message, tag, samples)?, value));
+    }
+    times.sort_by(|x, y| y.cmp(x));
+
+    let mut slowest = (Duration::from_secs(0), 0u8);
+    for &(_, value) in &times[..N_FINALISTS] {
+        tag[position] = value;
+        let time = median_time(client, message, tag, 4 * samples)?;
+        if time > slowest.0 {
+            slowest = (time, value);
+        }
+    }
+    Ok(slowest.1)
+}
+
+/**
+ * Recovers the tag of message byte by byte from the response times of the verifier, only one answer has to be OK
+ * A byte is kept when the same value is the slowest in two rounds of measurements. When the rounds disagree twice
+ * no value stands out, which happens after a wrong byte, so the previous byte is measured again.
+ * The last byte changes nothing in the time and is searched for with the answers themselves
+ * progress is called with the recovered prefix after every byte
+ * Returns None when no tag is accepted within the budget of rounds (e.g. with a constant-time comparison)
+ */
+pub fn recover_tag<F: FnMut(&[u8])>(
+    client: &mut Client,
+    message: &[u8],
+    tag_length: usize,
+    samples: usize,
+    mut progress: F
+) -> io::Result<Option<Vec<u8>>> {
+    let mut tag = vec![0u8; tag_length];
+    let mut position = 0;
+    let mut n_disagreements = 0;
+
+    for _ in 0..4 * tag_length {
+        if position + 1 < tag_length {
+            let value = slowest_byte(client, message, &mut tag, position, samples)?;
+            if value == slowest_byte(client, message, &mut tag, position, samples)? {
+                tag[position] = value;
+                progress(&tag[..=position]);
+                position += 1;
+                n_disagreements = 0;
+            } else {
+                n_disagreements += 1;
+                if n_disagreements == 2 {
+                    position = position.saturating_sub(1);
+                    n_disagreements = 0;
+                }
+            }
+            continue;
+        }
+
+        for value in 0..=255u8 {
+            tag[position] = value;
+            if client.verify(message, &tag)?.0 {
+                progress(&tag);
+                return Ok(Some(tag));
+            }
+        }
+        // one of the earlier bytes is wrong
+        position = position.saturating_sub(1);
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::random_bytes;
+    use super::*;
+
+    const MESSAGE: &[u8] = b"user=guest;admin=true";
+
+    fn start_service(service: Service) -> Client {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || accept(listener, service));
+        Client::connect(port).unwrap()
+    }
+
+    #[test]
+    fn early_exit() {
+        // a short tag and a per-byte delay far above the noise of the loopback interface
+        let service = Service {
+            key: random_bytes(32),
+            hash: HashFunction::Sha256,
+            tag_length: 2,
+            comparison: Comparison::EarlyExit,
+            byte_delay: Duration::from_millis(1),
+        };
+        let mut client = start_service(service.clone());
+
+        let tag = recover_tag(&mut client, MESSAGE, service.tag_length, 3, |_| ()).unwrap();
+        assert_eq!(tag, Some(service.tag(MESSAGE)));
+    }
+
+    #[test]
+    fn constant_time() {
+        // three bytes, so that guessing the two leading ones by chance is unlikely
+        let service = Service {
+            key: random_bytes(32),
+            hash: HashFunction::Sha256,
+            tag_length: 3,
+            comparison: Comparison::ConstantTime,
+            byte_delay: Duration::from_micros(50),
+        };
+        let mut client = start_service(service.clone());
+
+        assert_eq!(recover_tag(&mut client, MESSAGE, service.tag_length, 1, |_| ()).unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_requests() {
+        let service = Service {
+            key: random_bytes(32),
+            hash: HashFunction::Sha256,
+            tag_length: 16,
+            comparison: Comparison::EarlyExit,
+            byte_delay: Duration::from_micros(0),
+        };
+        assert_eq!(service.answer(&format!("VERIFY {} {}", hex::encode(MESSAGE), hex::encode(service.tag(MESSAGE)))), "OK\n");
+        assert_eq!(service.answer(&format!("VERIFY {} 00", hex::encode(MESSAGE))), "FAIL\n");
+        assert!(service.answer("VERIFY zz 00").starts_with("ERR"));
+        assert!(service.answer("RANGE 0 10").starts_with("ERR"));
+    }
+}
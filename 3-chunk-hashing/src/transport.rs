@@ -0,0 +1,230 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread;
+
+// line protocol between the server and the client, one request at a time on a connection:
+// the client sends `RANGE <offset> <length>\n`, the server answers `OK <n>\n` followed by n bytes
+// (fewer than asked at the end of the stream, none past it) or `ERR <reason>\n`
+pub const DEFAULT_PORT: u16 = 7878;
+
+// upper bound of a single range, so a request cannot make the server allocate without limit
+const MAX_RANGE_LENGTH: u64 = 1 << 24;
+
+/**
+ * Serves the file at path on localhost, every connection is handled by its own thread
+ * When corrupt_offset is given, the byte at that offset is flipped in every response which contains it
+ */
+pub fn serve(path: &str, port: u16, corrupt_offset: Option<u64>) -> io::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    eprintln!("Serving {} on {}", path, listener.local_addr()?);
+    accept(listener, path, corrupt_offset)
+}
+
+fn accept(listener: TcpListener, path: &str, corrupt_offset: Option<u64>) -> io::Result<()> {
+    for connection in listener.incoming() {
+        let connection = connection?;
+        let file = File::open(path)?;
+        thread::spawn(move || {
+            let peer = connection.peer_addr().map(|x| x.to_string()).unwrap_or_default();
+            if let Err(error) = handle_connection(file, connection, corrupt_offset) {
+                eprintln!("Connection {} closed: {}", peer, error);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn parse_request(line: &str) -> Result<(u64, u64), String> {
+    match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["RANGE", offset, length] => {
+            let offset = offset.parse().map_err(|_| format!("invalid offset {}", offset))?;
+            let length: u64 = length.parse().map_err(|_| format!("invalid length {}", length))?;
+            match length <= MAX_RANGE_LENGTH {
+                true => Ok((offset, length)),
+                false => Err(format!("length {} is larger than {}", length, MAX_RANGE_LENGTH)),
+            }
+        }
+        _ => Err(format!("invalid request {}", line.trim())),
+    }
+}
+
+fn handle_connection(mut file: File, connection: TcpStream, corrupt_offset: Option<u64>) -> io::Result<()> {
+    let mut reader = BufReader::new(connection.try_clone()?);
+    let mut writer = connection;
+    let file_length = file.metadata()?.len();
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        let (offset, length) = match parse_request(&line) {
+            Ok(range) => range,
+            Err(error) => {
+                writer.write_all(format!("ERR {}\n", error).as_bytes())?;
+                continue;
+            }
+        };
+
+        let start = offset.min(file_length);
+        let end = offset.saturating_add(length).min(file_length);
+        let mut data = vec![0u8; (end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut data)?;
+
+        if let Some(corrupt_offset) = corrupt_offset {
+            if (start..end).contains(&corrupt_offset) {
+                data[(corrupt_offset - start) as usize] ^= 0x01;
+            }
+        }
+
+        writer.write_all(format!("OK {}\n", data.len()).as_bytes())?;
+        writer.write_all(&data)?;
+        writer.flush()?;
+    }
+}
+
+/**
+ * Reads a remote stream sequentially, one range request of range_length bytes at a time,
+ * so a verifier can check every block as soon as it is downloaded
+ */
+pub struct RangeReader {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    range_length: u64,
+    offset: u64,
+    buffer: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl RangeReader {
+
+    /**
+     * The stream is read from offset, so a download can continue where an earlier one stopped
+     */
+    pub fn connect(port: u16, range_length: usize, offset: u64) -> io::Result<RangeReader> {
+        let connection = TcpStream::connect((Ipv4Addr::LOCALHOST, port))?;
+        Ok(RangeReader {
+            reader: BufReader::new(connection.try_clone()?),
+            writer: connection,
+            range_length: range_length as u64,
+            offset,
+            buffer: Vec::new(),
+            position: 0,
+            finished: false,
+        })
+    }
+
+    /**
+     * Requests the next range, an empty one means the end of the stream
+     */
+    fn fetch(&mut self) -> io::Result<()> {
+        self.writer.write_all(format!("RANGE {} {}\n", self.offset, self.range_length).as_bytes())?;
+        self.writer.flush()?;
+
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        let length = match line.trim().split_once(' ') {
+            Some(("OK", length)) => length.parse::<u64>().ok().filter(|x| *x <= self.range_length),
+            _ => None,
+        };
+        let length = length.ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected response from the server: {}", line.trim())
+        ))?;
+
+        self.buffer.resize(length as usize, 0);
+        self.reader.read_exact(&mut self.buffer)?;
+        self.position = 0;
+        self.offset += length;
+        self.finished = length == 0;
+        Ok(())
+    }
+}
+
+impl Read for RangeReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.buffer.len() && !self.finished {
+            self.fetch()?;
+        }
+        let n = buffer.len().min(self.buffer.len() - self.position);
+        buffer[..n].copy_from_slice(&self.buffer[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::TempPath;
+    use crate::encode::write_augmented;
+    use crate::hash::{HashFunction, Parameters};
+    use crate::verify::{verify_stream, VerifyError};
+    use super::*;
+
+    const PARAMETERS: Parameters = Parameters { chunk_length: 64, hash: HashFunction::Sha256 };
+
+    /**
+     * Serves the encoded payload on a free port, returns H0, the port and the served file
+     */
+    fn start_server(payload: &[u8], name: &str, corrupt_offset: Option<u64>) -> (Vec<u8>, u16, TempPath) {
+        let mut stream = Vec::new();
+        let (h0, _) = write_augmented(&mut io::Cursor::new(payload), &mut stream, &PARAMETERS).unwrap();
+        let file = TempPath::new(&format!("{}.bin", name));
+        fs::write(&file.0, stream).unwrap();
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let path = file.path();
+        thread::spawn(move || accept(listener, &path, corrupt_offset));
+        (h0, port, file)
+    }
+
+    #[test]
+    fn fetch() {
+        let payload: Vec<u8> = (0..1000).map(|x| x as u8).collect();
+        let (h0, port, _file) = start_server(&payload, "fetch", None);
+
+        let mut output = Vec::new();
+        let reader = RangeReader::connect(port, PARAMETERS.block_length(), 0).unwrap();
+        assert_eq!(verify_stream(&h0, &PARAMETERS, reader, &mut output).ok(), Some(1000));
+        assert_eq!(output, payload);
+    }
+
+    #[test]
+    fn corrupt_block() {
+        let payload: Vec<u8> = (0..1000).map(|x| x as u8).collect();
+        let corrupt_offset = 3 * PARAMETERS.block_length() as u64;
+        let (h0, port, _file) = start_server(&payload, "corrupt", Some(corrupt_offset));
+
+        // the blocks before the corrupted one are verified and written
+        let mut output = Vec::new();
+        let reader = RangeReader::connect(port, PARAMETERS.block_length(), 0).unwrap();
+        let result = verify_stream(&h0, &PARAMETERS, reader, &mut output);
+        assert!(matches!(result, Err(VerifyError::HashMismatch { block: 3, offset }) if offset == corrupt_offset));
+        assert_eq!(output, payload[..3 * PARAMETERS.chunk_length]);
+    }
+
+    #[test]
+    fn invalid_requests() {
+        let (_, port, _file) = start_server(b"payload", "requests", None);
+        let connection = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        let mut reader = BufReader::new(connection.try_clone().unwrap());
+        let mut writer = connection;
+
+        let mut answer = |request: &str| {
+            writer.write_all(request.as_bytes()).unwrap();
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            line
+        };
+        assert!(answer("GET /\n").starts_with("ERR"));
+        assert!(answer(&format!("RANGE 0 {}\n", MAX_RANGE_LENGTH + 1)).starts_with("ERR"));
+        assert_eq!(answer("RANGE 100 10\n"), "OK 0\n");
+    }
+}
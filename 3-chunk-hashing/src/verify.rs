@@ -0,0 +1,228 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use crate::hash::Parameters;
+use crate::encode::hash_chunk;
+
+/**
+ * Reasons for stopping the verification
+ */
+pub enum VerifyError {
+    Io(io::Error),
+    // hash of the block differs from the one announced by the previous block (H0 for block 0)
+    HashMismatch { block: usize, offset: u64 },
+    // the stream ends at offset, before the end of the block announced by the previous one
+    Truncated { block: usize, offset: u64 },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerifyError::Io(error) => write!(f, "I/O error: {}", error),
+            VerifyError::HashMismatch { block, offset } =>
+                write!(f, "block {} at byte offset {} does not match its hash", block, offset),
+            VerifyError::Truncated { block, offset } =>
+                write!(f, "stream ends at byte offset {} before the end of block {}", offset, block),
+        }
+    }
+}
+
+impl From<io::Error> for VerifyError {
+    fn from(error: io::Error) -> VerifyError {
+        VerifyError::Io(error)
+    }
+}
+
+/**
+ * Reads until the buffer is full or the stream ends, returns the number of bytes read
+ */
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut n_read = 0;
+    while n_read < buffer.len() {
+        match reader.read(&mut buffer[n_read..]) {
+            Ok(0) => break,
+            Ok(n) => n_read += n,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(n_read)
+}
+
+/**
+ * Position of a verifier in a hash chain, all it needs to continue with the rest of the stream
+ */
+#[derive(Clone, PartialEq)]
+pub struct ChainState {
+    // index of the next block
+    pub block: usize,
+    // byte offset of the next block in the stream
+    pub offset: u64,
+    // number of payload bytes verified so far
+    pub payload_length: u64,
+    // hash announced for the next block, H0 at the start and empty once the last block is verified
+    pub expected_hash: Vec<u8>,
+}
+
+impl ChainState {
+
+    pub fn start(h0: &[u8]) -> ChainState {
+        ChainState { block: 0, offset: 0, payload_length: 0, expected_hash: h0.to_vec() }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.expected_hash.is_empty()
+    }
+}
+
+/**
+ * Verifies a stream of blocks (chunk || hash of the next block, the last block is the chunk alone)
+ * against H0 and writes the payload of every block as soon as it is verified
+ * A full block (chunk and hash) announces another block, a shorter one is the last
+ * A stream cut inside the chunk of a block can not be told apart from a tampered last block and fails its hash
+ * The chunk length and hash function must be the ones the stream was encoded with
+ * Returns the number of verified payload bytes
+ */
+pub fn verify_stream<R: Read, W: Write>(
+    h0: &[u8],
+    parameters: &Parameters,
+    reader: R,
+    writer: W
+) -> Result<u64, VerifyError> {
+    let state = verify_from(ChainState::start(h0), parameters, reader, writer, |_| Ok(()))?;
+    Ok(state.payload_length)
+}
+
+/**
+ * Same as `verify_stream` but starts from state, the reader being positioned at state.offset in the stream
+ * on_block is called with the new state once the payload of a block is written and flushed,
+ * so persisting it there allows to resume after an interruption
+ * Returns the final state
+ */
+pub fn verify_from<R: Read, W: Write, F: FnMut(&ChainState) -> io::Result<()>>(
+    mut state: ChainState,
+    parameters: &Parameters,
+    mut reader: R,
+    mut writer: W,
+    mut on_block: F
+) -> Result<ChainState, VerifyError> {
+    let mut buffer = vec![0u8; parameters.block_length()];
+
+    while !state.is_complete() {
+        let n_read = read_full(&mut reader, &mut buffer)?;
+        if n_read == 0 {
+            return Err(VerifyError::Truncated { block: state.block, offset: state.offset });
+        }
+
+        let data = &buffer[..n_read];
+        if hash_chunk(data, parameters.hash) != state.expected_hash {
+            // the last block is the chunk alone, a longer short block was cut inside the hash of a full block
+            if n_read > parameters.chunk_length && n_read < buffer.len() {
+                return Err(VerifyError::Truncated { block: state.block, offset: state.offset + n_read as u64 });
+            }
+            return Err(VerifyError::HashMismatch { block: state.block, offset: state.offset });
+        }
+
+        let is_last = n_read < buffer.len();
+        let payload = match is_last {
+            true => data,
+            false => &data[..parameters.chunk_length],
+        };
+        writer.write_all(payload)?;
+        writer.flush()?;
+
+        state.block += 1;
+        state.offset += n_read as u64;
+        state.payload_length += payload.len() as u64;
+        state.expected_hash = match is_last {
+            true => Vec::new(),
+            false => data[parameters.chunk_length..].to_vec(),
+        };
+        on_block(&state)?;
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::encode::write_augmented;
+    use crate::hash::HashFunction;
+    use super::*;
+
+    const PARAMETERS: Parameters = Parameters { chunk_length: 64, hash: HashFunction::Sha256 };
+
+    fn encode(payload: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut stream: Vec<u8> = Vec::new();
+        let (h0, _) = write_augmented(&mut Cursor::new(payload), &mut stream, &PARAMETERS).unwrap();
+        (h0, stream)
+    }
+
+    #[test]
+    fn round_trip() {
+        for length in [0, 1, 63, 64, 65, 128, 1000] {
+            let payload: Vec<u8> = (0..length).map(|x| x as u8).collect();
+            let (h0, stream) = encode(&payload);
+            let mut output: Vec<u8> = Vec::new();
+            assert_eq!(verify_stream(&h0, &PARAMETERS, stream.as_slice(), &mut output).ok(), Some(length as u64));
+            assert_eq!(output, payload);
+        }
+    }
+
+    #[test]
+    fn empty_stream() {
+        let (h0, stream) = encode(&[]);
+        assert!(stream.is_empty());
+        assert!(h0.is_empty());
+
+        // an empty stream is not accepted for a non-empty payload
+        let (other_h0, _) = encode(b"payload");
+        assert!(matches!(verify_stream(&other_h0, &PARAMETERS, &[][..], io::sink()), Err(VerifyError::Truncated { block: 0, .. })));
+    }
+    #[test]
+    fn tampered_blocks() {
+        // 15 full blocks and a last one of 40 bytes
+        let payload: Vec<u8> = (0..1000).map(|x| x as u8).collect();
+        let (h0, stream) = encode(&payload);
+        let block_length = PARAMETERS.block_length();
+
+        for block in 0..16 {
+            let start = block * block_length;
+            let end = stream.len().min(start + block_length);
+            for position in [start, start + 20, end - 1] {
+                let mut tampered = stream.clone();
+                tampered[position] ^= 1;
+                let mut output: Vec<u8> = Vec::new();
+                let result = verify_stream(&h0, &PARAMETERS, tampered.as_slice(), &mut output);
+                assert!(matches!(result, Err(VerifyError::HashMismatch { block: x, offset }) if x == block && offset == start as u64),
+                    "byte {}", position);
+
+                // every block before the tampered one is written
+                assert_eq!(output, payload[..block * PARAMETERS.chunk_length]);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_streams() {
+        let payload: Vec<u8> = (0..1000).map(|x| x as u8).collect();
+        let (h0, stream) = encode(&payload);
+        let block_length = PARAMETERS.block_length();
+
+        for block in 0..15 {
+            let start = block * block_length;
+
+            // cut at the start of the block or inside its hash
+            for length in [start, start + PARAMETERS.chunk_length + 1, start + block_length - 1] {
+                let mut output: Vec<u8> = Vec::new();
+                let result = verify_stream(&h0, &PARAMETERS, &stream[..length], &mut output);
+                assert!(matches!(result, Err(VerifyError::Truncated { block: x, offset }) if x == block && offset == length as u64),
+                    "length {}", length);
+                assert_eq!(output, payload[..block * PARAMETERS.chunk_length]);
+            }
+
+            // cut inside the chunk, which might as well be the last block
+            let result = verify_stream(&h0, &PARAMETERS, &stream[..start + 10], io::sink());
+            assert!(matches!(result, Err(VerifyError::HashMismatch { block: x, offset }) if x == block && offset == start as u64));
+        }
+    }
+}
@@ -1,9 +1,12 @@
+use std::env;
+use std::process;
 use futures::future::join_all;
+use cipher_cli::output;
 
 /**
  * Bitwise xor of two binary strings (represented as byte vectors)
  */
-fn xor_bytes(bin_1: &Vec<u8>, bin_2: &Vec<u8>) -> Vec<u8> {
+fn xor_bytes(bin_1: &[u8], bin_2: &[u8]) -> Vec<u8> {
     bin_1.iter()
         .zip(bin_2)
         .map(|(x1, x2)| x1 ^ x2)
@@ -33,24 +36,24 @@ fn expand_padding(ciphertext_size: usize, padding_size: usize) -> Vec<u8> {
     let mut padding: Vec<u8> = vec![0; ciphertext_size];
 
     for i in 0..padding_size {
-        padding[ciphertext_size - 17 - i as usize] = padding_size as u8;
+        padding[ciphertext_size - 17 - i] = padding_size as u8;
     }
 
-    return padding
+    padding
 }
 
 /**
  * (Simplifies the subsequent xor operation)
  * Expands the already correctly guessed values
  */
-fn expand_guesses(ciphertext_size: usize, correct_guesses: &Vec<u8>) -> Vec<u8> {
+fn expand_guesses(ciphertext_size: usize, correct_guesses: &[u8]) -> Vec<u8> {
     let mut guesses_expanded: Vec<u8> = vec![0; ciphertext_size];
 
     for i in 0..16 {
-        guesses_expanded[ciphertext_size - 17 - i as usize] = correct_guesses[15 - i];
+        guesses_expanded[ciphertext_size - 17 - i] = correct_guesses[15 - i];
     }
 
-    return guesses_expanded;
+    guesses_expanded
 }
 
 /**
@@ -58,7 +61,7 @@ fn expand_guesses(ciphertext_size: usize, correct_guesses: &Vec<u8>) -> Vec<u8>
  */
 async fn guess_byte(
     ciphertext_bytes: &[u8],
-    correct_guesses: &Vec<u8>,
+    correct_guesses: &[u8],
     index: usize,
     client: &reqwest::Client
 ) -> Result<u8, Box<dyn std::error::Error>> {
@@ -69,7 +72,7 @@ async fn guess_byte(
     // get expanded padding and expanded correct guesses
     let padding_size = index + 1;
     let expanded_padding = expand_padding(len_ciphertext, padding_size);
-    let expanded_correct_guesses = expand_guesses(len_ciphertext, &correct_guesses);
+    let expanded_correct_guesses = expand_guesses(len_ciphertext, correct_guesses);
 
     chosen_ciphertext = xor_bytes(&chosen_ciphertext, &expanded_padding);
     chosen_ciphertext = xor_bytes(&chosen_ciphertext, &expanded_correct_guesses);
@@ -79,13 +82,13 @@ async fn guess_byte(
     let mut promises = Vec::new();
     for guess in 0..=255 {
         // xor the guess with the ciphertext
-        chosen_ciphertext[index_in_ciphertext] = chosen_ciphertext[index_in_ciphertext] ^ guess;
+        chosen_ciphertext[index_in_ciphertext] ^= guess;
 
         // call the request
         promises.push(get_status(chosen_ciphertext.clone(), client));
 
         // revert the ciphertext
-        chosen_ciphertext[index_in_ciphertext] = chosen_ciphertext[index_in_ciphertext] ^ guess;
+        chosen_ciphertext[index_in_ciphertext] ^= guess;
     }
 
     let status_codes: Vec<reqwest::StatusCode> = join_all(promises)
@@ -95,15 +98,14 @@ async fn guess_byte(
         .collect();
 
     // await the promises
-    for guess in 0..=255 {
-        let status = status_codes[guess];
+    for (guess, status) in status_codes.iter().enumerate() {
 
         // if any status is 404 or 200 that means a correct guess
-        if status == 404 {
+        if *status == 404 {
             return Ok(guess as u8);
-        }    
+        }
     }
-    return Ok(0);
+    Ok(0)
 }
 
 /**
@@ -128,9 +130,9 @@ async fn poa_last_block(
     for i in padding..16 {
         let index = 15 - i;
         correct_guesses[index as usize] = guess_byte(ciphertext_bytes, &correct_guesses, i as usize, client).await?;
-    } 
+    }
 
-    return Ok(correct_guesses);
+    Ok(correct_guesses)
 }
 
 /**
@@ -149,21 +151,26 @@ async fn poa_block(
         correct_guesses[index] = guess_byte(ciphertext_bytes, &correct_guesses, i, client).await?;
     }
 
-    return Ok(correct_guesses);
-}
-
-/**
- * Returns String based on provided binary string representing ascii text
- */
-fn bytes_to_ascii(bytes: Vec<u8>) -> String {
-    let plaintext_chars: Vec<char> = bytes.iter().map(|x| *x as char).collect();
-    let plaintext: String = plaintext_chars.iter().cloned().collect::<String>();
-    return plaintext;
+    Ok(correct_guesses)
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
+    // select how the recovered message is output
+    let args: Vec<String> = env::args().skip(1).collect();
+    let output_mode = match output::parse_output_args(&args) {
+        Ok((output_mode, remaining)) if remaining.is_empty() => output_mode,
+        Ok((_, remaining)) => {
+            eprintln!("Unexpected arguments {:?}, usage: cargo run [-- [--output mode] [--out-dir dir]]", remaining);
+            process::exit(2);
+        }
+        Err(error) => {
+            eprintln!("Invalid arguments: {}", error);
+            process::exit(2);
+        }
+    };
+
     // The MAC then CBC ciphertext, which we try to decrypt
     let ciphertext = "f20bdba6ff29eed7b046d1df9fb7000058b1ffb4210a580f748b4ac714c001bd4a61044426fb515dad3f21f18aa577c0bdf302936266926ff37dbf7035d5eeb4";
     let ciphertext_bytes = hex::decode(ciphertext)?;
@@ -179,17 +186,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     for i in 0..(n_blocks - 1) {
         let block_end = (i + 2) * 16;
         let message_block = poa_block(&ciphertext_bytes[0..block_end], &client).await?;
-        message_bytes.extend(message_block.clone());
-        println!("The message block no. {} is: {}", i, bytes_to_ascii(message_block));
+        println!("The message block no. {} is: {}", i, message_block.escape_ascii());
+        message_bytes.extend(message_block);
     }
 
     // Run the poa for the last block (uses different logic due to padding)
     let i = n_blocks - 1;
     let block_end = (i + 2) * 16;
     let message_block = poa_last_block(&ciphertext_bytes[0..block_end], &client).await?;
-    message_bytes.extend(message_block.clone());
-    println!("The message block no. {} is: {}", i, bytes_to_ascii(message_block));
+    println!("The message block no. {} is: {}", i, message_block.escape_ascii());
+    message_bytes.extend(message_block);
 
-    println!("The message is: \"{}\"", bytes_to_ascii(message_bytes));
+    println!("The message is:");
+    output::write_plaintext(&message_bytes, 0, &output_mode)?;
     Ok(())
 }
@@ -0,0 +1,189 @@
+use crate::key_schedule::expand_key;
+use crate::{Block, BlockCipher};
+
+/**
+ * Bitsliced state: plane b holds bit b of all 16 bytes (bit j of the plane is byte j of the block)
+ * Every transformation is a fixed sequence of AND, XOR and shifts of whole planes,
+ * there are no table lookups or branches depending on the key or the data
+ */
+type Planes = [u16; 8];
+
+// bits of the planes belonging to row r of the state are ROW_MASK << r
+const ROW_MASK: u16 = 0x1111;
+
+fn to_planes(bytes: &[u8]) -> Planes {
+    let mut planes = [0u16; 8];
+    for (bit, plane) in planes.iter_mut().enumerate() {
+        for (j, byte) in bytes.iter().enumerate() {
+            *plane |= (((byte >> bit) & 1) as u16) << j;
+        }
+    }
+    planes
+}
+
+fn from_planes(planes: &Planes) -> Block {
+    let mut block = [0u8; 16];
+    for (j, byte) in block.iter_mut().enumerate() {
+        for (bit, plane) in planes.iter().enumerate() {
+            *byte |= (((plane >> j) & 1) as u8) << bit;
+        }
+    }
+    block
+}
+
+fn xor_planes(a: &Planes, b: &Planes) -> Planes {
+    [0, 1, 2, 3, 4, 5, 6, 7].map(|x| a[x] ^ b[x])
+}
+
+/**
+ * Multiplication in GF(2^8) of all 16 bytes at once: carry-less product reduced by x^8 + x^4 + x^3 + x + 1
+ */
+fn gf_multiply(a: &Planes, b: &Planes) -> Planes {
+    let mut product = [0u16; 15];
+    for i in 0..8 {
+        for j in 0..8 {
+            product[i + j] ^= a[i] & b[j];
+        }
+    }
+
+    // x^k = x^(k-4) + x^(k-5) + x^(k-7) + x^(k-8) for k >= 8
+    for k in (8..15).rev() {
+        product[k - 4] ^= product[k];
+        product[k - 5] ^= product[k];
+        product[k - 7] ^= product[k];
+        product[k - 8] ^= product[k];
+    }
+    [0, 1, 2, 3, 4, 5, 6, 7].map(|x| product[x])
+}
+
+fn gf_square(a: &Planes) -> Planes {
+    gf_multiply(a, a)
+}
+
+/**
+ * Multiplicative inverse as a^254 (0 maps to 0)
+ */
+fn gf_invert(a: &Planes) -> Planes {
+    let a2 = gf_square(a);
+    let a3 = gf_multiply(&a2, a);
+    let a12 = gf_square(&gf_square(&a3));
+    let a15 = gf_multiply(&a12, &a3);
+    let a240 = gf_square(&gf_square(&gf_square(&gf_square(&a15))));
+    let a252 = gf_multiply(&a240, &a12);
+    gf_multiply(&a252, &a2)
+}
+
+/**
+ * Constant of the affine transformation, one whole plane per set bit
+ */
+fn constant_planes(constant: u8) -> Planes {
+    [0, 1, 2, 3, 4, 5, 6, 7].map(|x| 0u16.wrapping_sub(((constant >> x) & 1) as u16))
+}
+
+fn sub_bytes(state: &Planes) -> Planes {
+    let b = gf_invert(state);
+    let c = constant_planes(0x63);
+
+    // s = b + (b <<< 1) + (b <<< 2) + (b <<< 3) + (b <<< 4) + {63}
+    [0, 1, 2, 3, 4, 5, 6, 7].map(|i| b[i] ^ b[(i + 4) % 8] ^ b[(i + 5) % 8] ^ b[(i + 6) % 8] ^ b[(i + 7) % 8] ^ c[i])
+}
+
+fn inv_sub_bytes(state: &Planes) -> Planes {
+    let s = state;
+    let d = constant_planes(0x05);
+
+    // b = (s <<< 1) + (s <<< 3) + (s <<< 6) + {05}
+    let b = [0, 1, 2, 3, 4, 5, 6, 7].map(|i| s[(i + 7) % 8] ^ s[(i + 5) % 8] ^ s[(i + 2) % 8] ^ d[i]);
+    gf_invert(&b)
+}
+
+/**
+ * SubWord of the key schedule computed by the bitsliced S-box
+ */
+fn sub_word(word: [u8; 4]) -> [u8; 4] {
+    let block = from_planes(&sub_bytes(&to_planes(&word)));
+    [block[0], block[1], block[2], block[3]]
+}
+
+/**
+ * Row r moves left by r columns, which is a rotation of its bits by 4r positions
+ */
+fn shift_rows(state: &Planes) -> Planes {
+    state.map(|plane| (0..4).fold(0, |x, row| x | (plane & (ROW_MASK << row)).rotate_right(4 * row)))
+}
+
+fn inv_shift_rows(state: &Planes) -> Planes {
+    state.map(|plane| (0..4).fold(0, |x, row| x | (plane & (ROW_MASK << row)).rotate_left(4 * row)))
+}
+
+/**
+ * Moves the byte of row r + k of every column to row r
+ */
+fn rotate_rows(state: &Planes, k: u32) -> Planes {
+    let low = ROW_MASK * ((1 << (4 - k)) - 1);
+    state.map(|plane| ((plane >> k) & low) | ((plane << (4 - k)) & !low))
+}
+
+fn xtime(a: &Planes) -> Planes {
+    [a[7], a[0] ^ a[7], a[1], a[2] ^ a[7], a[3] ^ a[7], a[4], a[5], a[6]]
+}
+
+fn mix_columns(state: &Planes) -> Planes {
+    let rotated = rotate_rows(state, 1);
+    let all = xor_planes(&xor_planes(state, &rotated), &xor_planes(&rotate_rows(state, 2), &rotate_rows(state, 3)));
+
+    // 2a_r + 3a_(r+1) + a_(r+2) + a_(r+3) = a_r + (a0 + a1 + a2 + a3) + 2(a_r + a_(r+1))
+    xor_planes(&xor_planes(state, &all), &xtime(&xor_planes(state, &rotated)))
+}
+
+/**
+ * InvMixColumns is MixColumns after multiplying columns by {04}x^2 + {05}
+ */
+fn inv_mix_columns(state: &Planes) -> Planes {
+    let t = xtime(&xtime(&xor_planes(state, &rotate_rows(state, 2))));
+    mix_columns(&xor_planes(state, &t))
+}
+
+/**
+ * Constant-time bitsliced AES
+ * Slower than the table based variant (the S-box is computed as an inversion in GF(2^8)),
+ * but the running time and memory access pattern do not depend on the key or the data
+ */
+pub struct AesBitsliced {
+    round_keys: Vec<Planes>,
+}
+
+impl BlockCipher for AesBitsliced {
+
+    fn new(key: &[u8]) -> Result<AesBitsliced, String> {
+        let round_keys = expand_key(key, sub_word)?;
+        Ok(AesBitsliced { round_keys: round_keys.iter().map(|x| to_planes(x)).collect() })
+    }
+
+    fn encrypt_block(&self, block: &mut Block) {
+        let n_rounds = self.round_keys.len() - 1;
+
+        let mut state = xor_planes(&to_planes(block), &self.round_keys[0]);
+        for round in 1..=n_rounds {
+            state = shift_rows(&sub_bytes(&state));
+            if round != n_rounds {
+                state = mix_columns(&state);
+            }
+            state = xor_planes(&state, &self.round_keys[round]);
+        }
+        *block = from_planes(&state);
+    }
+
+    fn decrypt_block(&self, block: &mut Block) {
+        let n_rounds = self.round_keys.len() - 1;
+
+        let mut state = xor_planes(&to_planes(block), &self.round_keys[n_rounds]);
+        for round in (0..n_rounds).rev() {
+            state = xor_planes(&inv_sub_bytes(&inv_shift_rows(&state)), &self.round_keys[round]);
+            if round != 0 {
+                state = inv_mix_columns(&state);
+            }
+        }
+        *block = from_planes(&state);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::Block;
+
+// round constants x^(i-1) in GF(2^8), enough for AES-128 (the longest schedule in words)
+const ROUND_CONSTANTS: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+/**
+ * Number of rounds for the key length in bytes (10, 12 or 14)
+ */
+pub fn n_rounds(key_length: usize) -> Result<usize, String> {
+    match key_length {
+        16 => Ok(10),
+        24 => Ok(12),
+        32 => Ok(14),
+        other => Err(format!("AES key must be 16, 24 or 32 bytes long, got {}", other)),
+    }
+}
+
+/**
+ * KeyExpansion of FIPS-197, returns n_rounds + 1 round keys
+ * The S-box is passed in so the constant-time variant can expand keys without table lookups
+ */
+pub fn expand_key(key: &[u8], sub_word: fn([u8; 4]) -> [u8; 4]) -> Result<Vec<Block>, String> {
+    let n_rounds = n_rounds(key.len())?;
+    let key_words = key.len() / 4;
+    let n_words = 4 * (n_rounds + 1);
+
+    let mut words: Vec<[u8; 4]> = key.chunks(4)
+        .map(|x| [x[0], x[1], x[2], x[3]])
+        .collect();
+
+    for i in key_words..n_words {
+        let mut word = words[i - 1];
+        if i % key_words == 0 {
+            // RotWord, SubWord and the round constant
+            word = sub_word([word[1], word[2], word[3], word[0]]);
+            word[0] ^= ROUND_CONSTANTS[i / key_words - 1];
+        } else if key_words > 6 && i % key_words == 4 {
+            word = sub_word(word);
+        }
+
+        let previous = words[i - key_words];
+        words.push([word[0] ^ previous[0], word[1] ^ previous[1], word[2] ^ previous[2], word[3] ^ previous[3]]);
+    }
+
+    Ok(words.chunks(4)
+        .map(|x| {
+            let mut round_key = [0u8; 16];
+            for (column, word) in x.iter().enumerate() {
+                round_key[4 * column..4 * column + 4].copy_from_slice(word);
+            }
+            round_key
+        })
+        .collect())
+}
+
+/**
+ * Runs the AES-128 key schedule backwards from the round key of the given round to the master key
+ * w[i - 4] = w[i] + w[i - 1], with SubWord, RotWord and the round constant applied when i is a multiple of 4
+ */
+pub fn invert_key_schedule_128(round_key: &Block, round: usize, sub_word: fn([u8; 4]) -> [u8; 4]) -> Block {
+    let mut words: Vec<[u8; 4]> = vec![[0u8; 4]; 4 * (round + 1)];
+    for (column, word) in round_key.chunks(4).enumerate() {
+        words[4 * round + column] = [word[0], word[1], word[2], word[3]];
+    }
+
+    for i in (4..4 * (round + 1)).rev() {
+        let mut previous = words[i - 1];
+        if i % 4 == 0 {
+            previous = sub_word([previous[1], previous[2], previous[3], previous[0]]);
+            previous[0] ^= ROUND_CONSTANTS[i / 4 - 1];
+        }
+        words[i - 4] = [0, 1, 2, 3].map(|x| words[i][x] ^ previous[x]);
+    }
+
+    let mut master_key = [0u8; 16];
+    for (column, word) in words[0..4].iter().enumerate() {
+        master_key[4 * column..4 * column + 4].copy_from_slice(word);
+    }
+    master_key
+}
@@ -0,0 +1,138 @@
+/*!
+ * From-scratch AES-128/192/256 (FIPS-197)
+ *
+ * Three interchangeable implementations of the block cipher:
+ * - `Aes` - reference implementation following the specification step by step, exposes round-by-round state
+ * - `AesTables` - fast variant combining SubBytes, ShiftRows and MixColumns into lookup tables
+ * - `AesBitsliced` - constant-time variant without secret dependent table lookups or branches
+ *
+ * The `square` module attacks AES reduced to 4 rounds with the integral (square) attack
+ */
+
+pub mod bitsliced;
+pub mod key_schedule;
+pub mod reference;
+pub mod square;
+pub mod tables;
+
+pub use bitsliced::AesBitsliced;
+pub use reference::{Aes, RoundState};
+pub use tables::AesTables;
+
+pub const BLOCK_LENGTH: usize = 16;
+
+/**
+ * One 16 byte block, bytes are the state columns one after another (byte i is row i % 4, column i / 4)
+ */
+pub type Block = [u8; BLOCK_LENGTH];
+
+/**
+ * Common interface of the AES implementations
+ */
+pub trait BlockCipher: Sized {
+
+    /**
+     * Expands the key, which must be 16, 24 or 32 bytes long
+     */
+    fn new(key: &[u8]) -> Result<Self, String>;
+
+    /**
+     * Encrypts the block in place
+     */
+    fn encrypt_block(&self, block: &mut Block);
+
+    /**
+     * Decrypts the block in place
+     */
+    fn decrypt_block(&self, block: &mut Block);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+    use aes::cipher::{BlockEncrypt, NewBlockCipher};
+    use super::*;
+
+    const PLAINTEXT: &str = "00112233445566778899aabbccddeeff";
+
+    // FIPS-197 Appendix C.1 (AES-128), C.2 (AES-192) and C.3 (AES-256): key and ciphertext of PLAINTEXT
+    const FIPS197_VECTORS: [(&str, &str); 3] = [
+        ("000102030405060708090a0b0c0d0e0f", "69c4e0d86a7b0430d8cdb78070b4c55a"),
+        ("000102030405060708090a0b0c0d0e0f1011121314151617", "dda97ca4864cdfe06eaf70a0ec0d7191"),
+        ("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f", "8ea2b7ca516745bfeafc49904b496089"),
+    ];
+
+    fn block(hex_block: &str) -> Block {
+        hex::decode(hex_block).unwrap().try_into().unwrap()
+    }
+
+    fn check_fips197<C: BlockCipher>() {
+        for (key, ciphertext) in FIPS197_VECTORS.iter() {
+            let cipher = C::new(&hex::decode(key).unwrap()).unwrap();
+            let mut state = block(PLAINTEXT);
+            cipher.encrypt_block(&mut state);
+            assert_eq!(hex::encode(state), *ciphertext, "encryption with key {}", key);
+            cipher.decrypt_block(&mut state);
+            assert_eq!(hex::encode(state), PLAINTEXT, "decryption with key {}", key);
+        }
+    }
+
+    #[test]
+    fn fips197_reference() {
+        check_fips197::<Aes>();
+    }
+
+    #[test]
+    fn fips197_tables() {
+        check_fips197::<AesTables>();
+    }
+
+    #[test]
+    fn fips197_bitsliced() {
+        check_fips197::<AesBitsliced>();
+    }
+
+    #[test]
+    fn fips197_trace() {
+        // round 1 and the last round key of Appendix C.1
+        let trace = Aes::new(&hex::decode(FIPS197_VECTORS[0].0).unwrap()).unwrap().trace_encrypt(&block(PLAINTEXT));
+        assert_eq!(trace.len(), 11);
+        assert_eq!(trace[1].start, block("00102030405060708090a0b0c0d0e0f0"));
+        assert_eq!(trace[1].after_sub_bytes, Some(block("63cab7040953d051cd60e0e7ba70e18c")));
+        assert_eq!(trace[1].after_shift_rows, Some(block("6353e08c0960e104cd70b751bacad0e7")));
+        assert_eq!(trace[1].after_mix_columns, Some(block("5f72641557f5bc92f7be3b291db9f91a")));
+        assert_eq!(trace[1].round_key, block("d6aa74fdd2af72fadaa678f1d6ab76fe"));
+        assert_eq!(trace[10].round_key, block("13111d7fe3944a17f307a78b4d2b30c5"));
+        assert_eq!(trace[10].after_mix_columns, None);
+        assert_eq!(trace[10].output, block(FIPS197_VECTORS[0].1));
+    }
+
+    fn aes_crate_encrypt(key: &[u8], block: &Block) -> Block {
+        let mut output = aes::Block::from(*block);
+        match key.len() {
+            16 => aes::Aes128::new_from_slice(key).unwrap().encrypt_block(&mut output),
+            24 => aes::Aes192::new_from_slice(key).unwrap().encrypt_block(&mut output),
+            _ => aes::Aes256::new_from_slice(key).unwrap().encrypt_block(&mut output),
+        }
+        output.into()
+    }
+
+    #[test]
+    fn same_as_aes_crate() {
+        for key_length in [16, 24, 32] {
+            for _ in 0..20 {
+                let mut key = vec![0u8; key_length];
+                let mut plaintext = [0u8; BLOCK_LENGTH];
+                getrandom::getrandom(&mut key).unwrap();
+                getrandom::getrandom(&mut plaintext).unwrap();
+                let expected = aes_crate_encrypt(&key, &plaintext);
+
+                let (mut reference, mut tables, mut bitsliced) = (plaintext, plaintext, plaintext);
+                Aes::new(&key).unwrap().encrypt_block(&mut reference);
+                AesTables::new(&key).unwrap().encrypt_block(&mut tables);
+                AesBitsliced::new(&key).unwrap().encrypt_block(&mut bitsliced);
+                assert_eq!((reference, tables, bitsliced), (expected, expected, expected), "key {}", hex::encode(&key));
+            }
+        }
+    }
+}
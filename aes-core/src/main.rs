@@ -0,0 +1,54 @@
+use std::env;
+use std::process;
+use aes_core::square;
+
+const USAGE: &str = "usage: cargo run -- <command> <args>
+commands:
+    square [key_hex]";
+
+
+/**
+ * Attacks the given key (random if none), returns whether the master key was recovered
+ */
+fn run_square(args: &[String]) -> Result<bool, String> {
+    let key = match args {
+        [] => {
+            let mut key = vec![0u8; 16];
+            getrandom::getrandom(&mut key).map_err(|x| format!("unable to read random bytes: {}", x))?;
+            key
+        }
+        [key_hex] => hex::decode(key_hex).map_err(|x| format!("invalid key: {}", x))?,
+        _ => return Err("expected at most a key".to_string()),
+    };
+    println!("Secret key: {}", hex::encode(&key));
+
+    let mut oracle = square::Oracle::new(&key)?;
+    let (round_key, master_key) = square::attack(&mut oracle)?;
+    println!("Round {} key: {}", square::N_ROUNDS, hex::encode(round_key));
+    println!("Master key: {}", hex::encode(master_key));
+    println!("Chosen plaintexts: {}", oracle.n_queries());
+    Ok(master_key.to_vec() == key)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(|x| x.as_str()) {
+        Some("square") => match run_square(&args[2..]) {
+            Ok(true) => (),
+            Ok(false) => process::exit(1),
+            Err(error) => {
+                eprintln!("Square attack failed: {}", error);
+                process::exit(1);
+            }
+        },
+        Some(command) => {
+            eprintln!("Unknown command {}\n{}", command, USAGE);
+            process::exit(2);
+        }
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
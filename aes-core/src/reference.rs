@@ -0,0 +1,262 @@
+use crate::key_schedule::expand_key;
+use crate::{Block, BlockCipher};
+
+/**
+ * The AES S-box: multiplicative inverse in GF(2^8) followed by an affine transformation
+ */
+pub const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+/**
+ * Inverse of the S-box, computed at compile time
+ */
+pub const INV_SBOX: [u8; 256] = invert_sbox();
+
+const fn invert_sbox() -> [u8; 256] {
+    let mut inverse = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        inverse[SBOX[i] as usize] = i as u8;
+        i += 1;
+    }
+    inverse
+}
+
+/**
+ * State of the cipher during one encryption round, as listed in Appendix C of FIPS-197
+ * Round 0 only adds the round key, the last round has no MixColumns
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoundState {
+    pub round: usize,
+    pub start: Block,
+    pub after_sub_bytes: Option<Block>,
+    pub after_shift_rows: Option<Block>,
+    pub after_mix_columns: Option<Block>,
+    pub round_key: Block,
+    // state after AddRoundKey, the start of the next round
+    pub output: Block,
+}
+
+/**
+ * Multiplication by x in GF(2^8) modulo x^8 + x^4 + x^3 + x + 1
+ */
+pub fn xtime(byte: u8) -> u8 {
+    (byte << 1) ^ (0x1b & 0u8.wrapping_sub(byte >> 7))
+}
+
+/**
+ * Multiplication in GF(2^8)
+ */
+pub fn gf_multiply(a: u8, b: u8) -> u8 {
+    let mut product = 0u8;
+    let mut a = a;
+    for bit in 0..8 {
+        product ^= a & 0u8.wrapping_sub((b >> bit) & 1);
+        a = xtime(a);
+    }
+    product
+}
+
+/**
+ * SubWord of the key schedule
+ */
+pub fn sub_word(word: [u8; 4]) -> [u8; 4] {
+    word.map(|x| SBOX[x as usize])
+}
+
+pub fn sub_bytes(state: &mut Block) {
+    for byte in state.iter_mut() {
+        *byte = SBOX[*byte as usize];
+    }
+}
+
+pub fn inv_sub_bytes(state: &mut Block) {
+    for byte in state.iter_mut() {
+        *byte = INV_SBOX[*byte as usize];
+    }
+}
+
+/**
+ * Rotates row r of the state left by r positions
+ */
+pub fn shift_rows(state: &mut Block) {
+    let original = *state;
+    for column in 0..4 {
+        for row in 0..4 {
+            state[4 * column + row] = original[4 * ((column + row) % 4) + row];
+        }
+    }
+}
+
+pub fn inv_shift_rows(state: &mut Block) {
+    let original = *state;
+    for column in 0..4 {
+        for row in 0..4 {
+            state[4 * ((column + row) % 4) + row] = original[4 * column + row];
+        }
+    }
+}
+
+/**
+ * Multiplies every column by the polynomial {03}x^3 + {01}x^2 + {01}x + {02}
+ */
+pub fn mix_columns(state: &mut Block) {
+    for column in state.chunks_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        let all = a0 ^ a1 ^ a2 ^ a3;
+
+        // 2a_i + 3a_(i+1) + a_(i+2) + a_(i+3) = a_i + (a0 + a1 + a2 + a3) + 2(a_i + a_(i+1))
+        column[0] = a0 ^ all ^ xtime(a0 ^ a1);
+        column[1] = a1 ^ all ^ xtime(a1 ^ a2);
+        column[2] = a2 ^ all ^ xtime(a2 ^ a3);
+        column[3] = a3 ^ all ^ xtime(a3 ^ a0);
+    }
+}
+
+/**
+ * Multiplies every column by the polynomial {0b}x^3 + {0d}x^2 + {09}x + {0e}
+ */
+pub fn inv_mix_columns(state: &mut Block) {
+    for column in state.chunks_mut(4) {
+        let a = [column[0], column[1], column[2], column[3]];
+        for (row, byte) in column.iter_mut().enumerate() {
+            *byte = gf_multiply(a[row], 0x0e)
+                ^ gf_multiply(a[(row + 1) % 4], 0x0b)
+                ^ gf_multiply(a[(row + 2) % 4], 0x0d)
+                ^ gf_multiply(a[(row + 3) % 4], 0x09);
+        }
+    }
+}
+
+pub fn add_round_key(state: &mut Block, round_key: &Block) {
+    for (byte, key_byte) in state.iter_mut().zip(round_key) {
+        *byte ^= key_byte;
+    }
+}
+
+/**
+ * Reference AES, each round is computed by the four transformations of the specification
+ */
+pub struct Aes {
+    round_keys: Vec<Block>,
+}
+
+impl Aes {
+
+    /**
+     * Round keys of the expanded key (round 0 is the whitening key)
+     */
+    pub fn round_keys(&self) -> &[Block] {
+        &self.round_keys
+    }
+
+    pub fn n_rounds(&self) -> usize {
+        self.round_keys.len() - 1
+    }
+
+    /**
+     * Encrypts the block with only the first n_rounds rounds, the last of which omits MixColumns
+     * Reduced-round variants are used by cryptanalytic attacks
+     */
+    pub fn encrypt_rounds(&self, block: &mut Block, n_rounds: usize) {
+        let n_rounds = n_rounds.min(self.n_rounds());
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..=n_rounds {
+            sub_bytes(block);
+            shift_rows(block);
+            if round != n_rounds {
+                mix_columns(block);
+            }
+            add_round_key(block, &self.round_keys[round]);
+        }
+    }
+
+    /**
+     * Encrypts the block and records the state after every step of every round
+     */
+    pub fn trace_encrypt(&self, block: &Block) -> Vec<RoundState> {
+        let mut state = *block;
+        add_round_key(&mut state, &self.round_keys[0]);
+
+        let mut trace = vec![RoundState {
+            round: 0,
+            start: *block,
+            after_sub_bytes: None,
+            after_shift_rows: None,
+            after_mix_columns: None,
+            round_key: self.round_keys[0],
+            output: state,
+        }];
+
+        for round in 1..=self.n_rounds() {
+            let start = state;
+            sub_bytes(&mut state);
+            let after_sub_bytes = Some(state);
+            shift_rows(&mut state);
+            let after_shift_rows = Some(state);
+            let after_mix_columns = match round == self.n_rounds() {
+                true => None,
+                false => {
+                    mix_columns(&mut state);
+                    Some(state)
+                }
+            };
+            add_round_key(&mut state, &self.round_keys[round]);
+
+            trace.push(RoundState {
+                round,
+                start,
+                after_sub_bytes,
+                after_shift_rows,
+                after_mix_columns,
+                round_key: self.round_keys[round],
+                output: state,
+            });
+        }
+        trace
+    }
+}
+
+impl BlockCipher for Aes {
+
+    fn new(key: &[u8]) -> Result<Aes, String> {
+        Ok(Aes { round_keys: expand_key(key, sub_word)? })
+    }
+
+    fn encrypt_block(&self, block: &mut Block) {
+        self.encrypt_rounds(block, self.n_rounds());
+    }
+
+    /**
+     * Inverse cipher: the rounds in reverse order with the inverse transformations
+     */
+    fn decrypt_block(&self, block: &mut Block) {
+        let n_rounds = self.n_rounds();
+        add_round_key(block, &self.round_keys[n_rounds]);
+        for round in (0..n_rounds).rev() {
+            inv_shift_rows(block);
+            inv_sub_bytes(block);
+            add_round_key(block, &self.round_keys[round]);
+            if round != 0 {
+                inv_mix_columns(block);
+            }
+        }
+    }
+}
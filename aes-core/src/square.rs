@@ -0,0 +1,128 @@
+use crate::key_schedule::invert_key_schedule_128;
+use crate::reference::{sub_word, INV_SBOX};
+use crate::{Aes, Block, BlockCipher};
+
+// the attacked cipher: 3 full rounds and a last round without MixColumns
+pub const N_ROUNDS: usize = 4;
+
+// a wrong key byte passes one Λ-set with probability 1/256, a few sets are almost always enough
+const MAX_LAMBDA_SETS: usize = 32;
+
+/**
+ * Encryption oracle of the reduced-round AES-128 under a secret key
+ */
+pub struct Oracle {
+    cipher: Aes,
+    n_queries: usize,
+}
+
+impl Oracle {
+
+    pub fn new(key: &[u8]) -> Result<Oracle, String> {
+        if key.len() != 16 {
+            return Err(format!("the square attack targets AES-128, key must be 16 bytes long, got {}", key.len()));
+        }
+        Ok(Oracle { cipher: Aes::new(key)?, n_queries: 0 })
+    }
+
+    pub fn encrypt(&mut self, plaintext: &Block) -> Block {
+        self.n_queries += 1;
+        let mut block = *plaintext;
+        self.cipher.encrypt_rounds(&mut block, N_ROUNDS);
+        block
+    }
+
+    /**
+     * Number of chosen plaintexts encrypted so far
+     */
+    pub fn n_queries(&self) -> usize {
+        self.n_queries
+    }
+}
+
+/**
+ * Encrypts a Λ-set: 256 plaintexts whose first byte takes every value and the other bytes equal the constant
+ * After 3 rounds every byte of the state sums (xors) to zero over the set
+ */
+fn encrypt_lambda_set(oracle: &mut Oracle, constant: u8) -> Vec<Block> {
+    (0..=255u8)
+        .map(|x| {
+            let mut plaintext = [constant; 16];
+            plaintext[0] = x;
+            oracle.encrypt(&plaintext)
+        })
+        .collect()
+}
+
+/**
+ * Checks a guess of one byte of the last round key: the byte is undone through AddRoundKey and SubBytes
+ * (ShiftRows only moves it), the guess survives if the partially decrypted bytes are balanced
+ */
+fn is_balanced(ciphertexts: &[Block], position: usize, key_byte: u8) -> bool {
+    ciphertexts.iter()
+        .fold(0u8, |sum, x| sum ^ INV_SBOX[(x[position] ^ key_byte) as usize]) == 0
+}
+
+/**
+ * Recovers the round key of the last round byte by byte, filtering guesses with Λ-sets until one remains
+ */
+pub fn recover_last_round_key(oracle: &mut Oracle) -> Result<Block, String> {
+    let mut candidates: Vec<Vec<u8>> = vec![(0..=255u8).collect(); 16];
+
+    for constant in 0..MAX_LAMBDA_SETS {
+        if candidates.iter().all(|x| x.len() == 1) {
+            break;
+        }
+
+        let ciphertexts = encrypt_lambda_set(oracle, constant as u8);
+        for (position, guesses) in candidates.iter_mut().enumerate() {
+            guesses.retain(|x| is_balanced(&ciphertexts, position, *x));
+        }
+    }
+
+    let mut round_key = [0u8; 16];
+    for (position, guesses) in candidates.iter().enumerate() {
+        match guesses.as_slice() {
+            [key_byte] => round_key[position] = *key_byte,
+            [] => return Err(format!("no key byte at position {} is consistent with the Λ-sets", position)),
+            _ => return Err(format!("{} key bytes at position {} remain after {} Λ-sets",
+                guesses.len(), position, MAX_LAMBDA_SETS)),
+        }
+    }
+    Ok(round_key)
+}
+
+/**
+ * Square attack on 4-round AES-128: recovers the last round key and inverts the key schedule
+ * Returns the last round key and the master key
+ */
+pub fn attack(oracle: &mut Oracle) -> Result<(Block, Block), String> {
+    let round_key = recover_last_round_key(oracle)?;
+    let master_key = invert_key_schedule_128(&round_key, N_ROUNDS, sub_word);
+    Ok((round_key, master_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recovers(key: &[u8]) {
+        let mut oracle = Oracle::new(key).unwrap();
+        let (_, master_key) = attack(&mut oracle).unwrap();
+        assert_eq!(master_key.to_vec(), key, "key {} not recovered", hex::encode(key));
+    }
+
+    #[test]
+    fn fips197_key() {
+        recovers(&hex::decode("000102030405060708090a0b0c0d0e0f").unwrap());
+    }
+
+    #[test]
+    fn random_keys() {
+        for _ in 0..20 {
+            let mut key = [0u8; 16];
+            getrandom::getrandom(&mut key).unwrap();
+            recovers(&key);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::sync::OnceLock;
+use crate::key_schedule::expand_key;
+use crate::reference::{gf_multiply, inv_mix_columns, sub_word, INV_SBOX, SBOX};
+use crate::{Block, BlockCipher};
+
+/**
+ * Round lookup tables, table i is table 0 rotated right by i bytes
+ * Encryption tables combine SubBytes and MixColumns, decryption tables their inverses
+ * Columns are packed into big-endian words (row 0 in the most significant byte)
+ */
+struct Tables {
+    encryption: [[u32; 256]; 4],
+    decryption: [[u32; 256]; 4],
+}
+
+static TABLES: OnceLock<Tables> = OnceLock::new();
+
+/**
+ * Packs the column of coefficients multiplied by the byte into a word
+ */
+fn column_word(byte: u8, coefficients: [u8; 4]) -> u32 {
+    u32::from_be_bytes(coefficients.map(|x| gf_multiply(byte, x)))
+}
+
+fn tables() -> &'static Tables {
+    TABLES.get_or_init(|| {
+        let mut tables = Tables { encryption: [[0u32; 256]; 4], decryption: [[0u32; 256]; 4] };
+        for x in 0..256 {
+            let encryption = column_word(SBOX[x], [0x02, 0x01, 0x01, 0x03]);
+            let decryption = column_word(INV_SBOX[x], [0x0e, 0x09, 0x0d, 0x0b]);
+            for i in 0..4 {
+                tables.encryption[i][x] = encryption.rotate_right(8 * i as u32);
+                tables.decryption[i][x] = decryption.rotate_right(8 * i as u32);
+            }
+        }
+        tables
+    })
+}
+
+fn block_to_words(block: &Block) -> [u32; 4] {
+    [0, 1, 2, 3].map(|x| u32::from_be_bytes([block[4 * x], block[4 * x + 1], block[4 * x + 2], block[4 * x + 3]]))
+}
+
+fn words_to_block(words: &[u32; 4]) -> Block {
+    let mut block = [0u8; 16];
+    for (column, word) in words.iter().enumerate() {
+        block[4 * column..4 * column + 4].copy_from_slice(&word.to_be_bytes());
+    }
+    block
+}
+
+/**
+ * Byte of the given row (0 is the most significant) of the word
+ */
+fn row_byte(word: u32, row: usize) -> usize {
+    ((word >> (24 - 8 * row)) & 0xff) as usize
+}
+
+/**
+ * Table based AES, one round costs 16 table lookups
+ * Lookups are indexed by secret data, so the variant is fast but leaks through cache timing
+ */
+pub struct AesTables {
+    encryption_keys: Vec<[u32; 4]>,
+    // round keys of the equivalent inverse cipher (InvMixColumns applied to the inner ones)
+    decryption_keys: Vec<[u32; 4]>,
+}
+
+impl BlockCipher for AesTables {
+
+    fn new(key: &[u8]) -> Result<AesTables, String> {
+        let round_keys = expand_key(key, sub_word)?;
+        let n_rounds = round_keys.len() - 1;
+
+        let decryption_keys = round_keys.iter()
+            .rev()
+            .enumerate()
+            .map(|(i, round_key)| {
+                let mut round_key = *round_key;
+                if i != 0 && i != n_rounds {
+                    inv_mix_columns(&mut round_key);
+                }
+                block_to_words(&round_key)
+            })
+            .collect();
+
+        Ok(AesTables {
+            encryption_keys: round_keys.iter().map(block_to_words).collect(),
+            decryption_keys,
+        })
+    }
+
+    fn encrypt_block(&self, block: &mut Block) {
+        let te = &tables().encryption;
+        let n_rounds = self.encryption_keys.len() - 1;
+
+        let mut state = block_to_words(block);
+        for (word, key) in state.iter_mut().zip(&self.encryption_keys[0]) {
+            *word ^= key;
+        }
+
+        for round in 1..n_rounds {
+            let key = &self.encryption_keys[round];
+            state = [0, 1, 2, 3].map(|c| {
+                te[0][row_byte(state[c], 0)]
+                    ^ te[1][row_byte(state[(c + 1) % 4], 1)]
+                    ^ te[2][row_byte(state[(c + 2) % 4], 2)]
+                    ^ te[3][row_byte(state[(c + 3) % 4], 3)]
+                    ^ key[c]
+            });
+        }
+
+        // last round without MixColumns
+        let key = &self.encryption_keys[n_rounds];
+        state = [0, 1, 2, 3].map(|c| {
+            u32::from_be_bytes([0, 1, 2, 3].map(|row| SBOX[row_byte(state[(c + row) % 4], row)])) ^ key[c]
+        });
+        *block = words_to_block(&state);
+    }
+
+    fn decrypt_block(&self, block: &mut Block) {
+        let td = &tables().decryption;
+        let n_rounds = self.decryption_keys.len() - 1;
+
+        let mut state = block_to_words(block);
+        for (word, key) in state.iter_mut().zip(&self.decryption_keys[0]) {
+            *word ^= key;
+        }
+
+        for round in 1..n_rounds {
+            let key = &self.decryption_keys[round];
+            state = [0, 1, 2, 3].map(|c| {
+                td[0][row_byte(state[c], 0)]
+                    ^ td[1][row_byte(state[(c + 3) % 4], 1)]
+                    ^ td[2][row_byte(state[(c + 2) % 4], 2)]
+                    ^ td[3][row_byte(state[(c + 1) % 4], 3)]
+                    ^ key[c]
+            });
+        }
+
+        let key = &self.decryption_keys[n_rounds];
+        state = [0, 1, 2, 3].map(|c| {
+            u32::from_be_bytes([0, 1, 2, 3].map(|row| INV_SBOX[row_byte(state[(c + 4 - row) % 4], row)])) ^ key[c]
+        });
+        *block = words_to_block(&state);
+    }
+}
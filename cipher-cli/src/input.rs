@@ -0,0 +1,247 @@
+use std::fs;
+use serde::Deserialize;
+use crate::kdf::KeyDerivation;
+
+/**
+ * Encoding of the key, IV and ciphertext fields of a record
+ */
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+/**
+ * One record of the structured (JSON) input file
+ * The IV (`nonce` is accepted too) is either given separately or prefixed to the ciphertext
+ * The key can be omitted when it is derived from a passphrase
+ */
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Record {
+    pub key: Option<String>,
+    #[serde(alias = "nonce")]
+    pub iv: Option<String>,
+    pub ciphertext: String,
+    #[serde(default)]
+    pub encoding: Encoding,
+    pub mode: Option<String>,
+    pub expected_plaintext: Option<String>,
+}
+
+/**
+ * Decodes a field of the record with the given index in the given encoding
+ */
+pub fn decode_field(value: &str, encoding: Encoding, field: &str, index: usize) -> Result<Vec<u8>, String> {
+    let decoded = match encoding {
+        Encoding::Hex => hex::decode(value.trim()).map_err(|x| x.to_string()),
+        Encoding::Base64 => base64::decode(value.trim()).map_err(|x| x.to_string()),
+    };
+    decoded.map_err(|x| format!("record {}: {} is not valid: {}", index, field, x))
+}
+
+/**
+ * Reads the structured input: a JSON array of records
+ * to_message validates a record (with its index) and converts it into a message of the project
+ */
+pub fn read_records<M, F>(path: &str, key_derivation: Option<&KeyDerivation>, to_message: F) -> Result<Vec<M>, String>
+where
+    F: Fn(Record, usize, Option<&KeyDerivation>) -> Result<M, String>
+{
+    let content = fs::read_to_string(path)
+        .map_err(|x| format!("unable to read {}: {}", path, x))?;
+    let records: Vec<Record> = serde_json::from_str(&content)
+        .map_err(|x| format!("{} is not a valid input file: {}", path, x))?;
+
+    records.into_iter()
+        .enumerate()
+        .map(|(index, record)| to_message(record, index, key_derivation))
+        .collect()
+}
+
+/**
+ * Reads lines of a legacy input file, ignoring trailing empty lines
+ */
+fn read_lines(path: &str) -> Result<Vec<String>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|x| format!("unable to read {}: {}", path, x))?;
+
+    Ok(content.trim_end().lines().map(|x| x.trim().to_string()).collect())
+}
+
+/**
+ * Converts lines of hex ciphertexts (and keys, if given) into messages
+ */
+fn lines_to_messages<M, F>(
+    ciphertexts: Vec<String>,
+    keys: Vec<Option<String>>,
+    key_derivation: Option<&KeyDerivation>,
+    to_message: F
+) -> Result<Vec<M>, String>
+where
+    F: Fn(Record, usize, Option<&KeyDerivation>) -> Result<M, String>
+{
+    ciphertexts.into_iter()
+        .zip(keys)
+        .enumerate()
+        .map(|(index, (ciphertext, key))| {
+            let record = Record {
+                key,
+                iv: None,
+                ciphertext,
+                encoding: Encoding::Hex,
+                mode: None,
+                expected_plaintext: None,
+            };
+            to_message(record, index, key_derivation)
+        })
+        .collect()
+}
+
+/**
+ * Reads the legacy input: two files with the same number of lines of hex keys and hex IV-prefixed ciphertexts
+ */
+pub fn read_legacy<M, F>(ciphertexts_path: &str, keys_path: &str, to_message: F) -> Result<Vec<M>, String>
+where
+    F: Fn(Record, usize, Option<&KeyDerivation>) -> Result<M, String>
+{
+    let ciphertexts = read_lines(ciphertexts_path)?;
+    let keys = read_lines(keys_path)?;
+
+    if ciphertexts.len() != keys.len() {
+        return Err(format!("{} has {} lines but {} has {} lines",
+            ciphertexts_path, ciphertexts.len(), keys_path, keys.len()));
+    }
+
+    lines_to_messages(ciphertexts, keys.into_iter().map(Some).collect(), None, to_message)
+}
+
+/**
+ * Reads lines of hex ciphertexts whose keys are derived from a passphrase
+ */
+pub fn read_ciphertexts<M, F>(ciphertexts_path: &str, key_derivation: &KeyDerivation, to_message: F) -> Result<Vec<M>, String>
+where
+    F: Fn(Record, usize, Option<&KeyDerivation>) -> Result<M, String>
+{
+    let ciphertexts = read_lines(ciphertexts_path)?;
+    let keys = vec![None; ciphertexts.len()];
+
+    lines_to_messages(ciphertexts, keys, Some(key_derivation), to_message)
+}
+
+/**
+ * Reads either the structured input (one path) or the legacy input (two paths)
+ * With a passphrase, a single non-JSON path is a file of ciphertexts without keys
+ */
+pub fn read_input<M, F>(paths: &[String], key_derivation: Option<&KeyDerivation>, to_message: F) -> Result<Vec<M>, String>
+where
+    F: Fn(Record, usize, Option<&KeyDerivation>) -> Result<M, String>
+{
+    match (paths, key_derivation) {
+        ([], None) => read_legacy("./input_ciphertexts.txt", "./input_keys.txt", to_message),
+        ([], Some(key_derivation)) => read_ciphertexts("./input_ciphertexts.txt", key_derivation, to_message),
+        ([path], _) if path.ends_with(".json") => read_records(path, key_derivation, to_message),
+        ([path], Some(key_derivation)) => read_ciphertexts(path, key_derivation, to_message),
+        ([path], None) => read_records(path, None, to_message),
+        ([ciphertexts_path, keys_path], None) => read_legacy(ciphertexts_path, keys_path, to_message),
+        ([_, _], Some(_)) => Err("keys file can not be combined with a passphrase".to_string()),
+        _ => Err("expected either one structured input file or a ciphertexts file and a keys file".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::PathBuf;
+    use std::process;
+    use super::*;
+
+    /**
+     * File in the temporary directory, removed when dropped
+     */
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, content: &str) -> TempFile {
+            let path = env::temp_dir().join(format!("cipher-cli-{}-{}", process::id(), name));
+            fs::write(&path, content).unwrap();
+            TempFile(path)
+        }
+
+        fn path(&self) -> String {
+            self.0.display().to_string()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    // keeps the fields the tests look at
+    fn fields(record: Record, index: usize, _: Option<&KeyDerivation>) -> Result<(usize, Option<String>, Option<String>, String), String> {
+        Ok((index, record.key, record.iv, record.ciphertext))
+    }
+
+    #[test]
+    fn legacy_line_counts() {
+        let ciphertexts = TempFile::new("count-ciphertexts.txt", "00\n01\n02\n");
+        let keys = TempFile::new("count-keys.txt", "aa\nbb\n");
+        let error = read_legacy(&ciphertexts.path(), &keys.path(), fields).err().unwrap();
+        assert!(error.contains("has 3 lines but") && error.ends_with("has 2 lines"), "{}", error);
+    }
+
+    #[test]
+    fn legacy_trailing_newlines() {
+        let ciphertexts = TempFile::new("newline-ciphertexts.txt", "00 \n01\n\n\n");
+        let keys = TempFile::new("newline-keys.txt", "aa\r\nbb");
+        let messages = read_legacy(&ciphertexts.path(), &keys.path(), fields).unwrap();
+        assert_eq!(messages, [
+            (0, Some("aa".to_string()), None, "00".to_string()),
+            (1, Some("bb".to_string()), None, "01".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn records() {
+        let input = TempFile::new("records.json", r#"[
+            {"key": "aa", "iv": "bb", "ciphertext": "cc"},
+            {"key": "aa", "nonce": "dd", "ciphertext": "ee", "encoding": "hex"}
+        ]"#);
+        let messages = read_input(&[input.path()], None, fields).unwrap();
+        assert_eq!(messages, [
+            (0, Some("aa".to_string()), Some("bb".to_string()), "cc".to_string()),
+            (1, Some("aa".to_string()), Some("dd".to_string()), "ee".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let input = TempFile::new("unknown.json", r#"[{"key": "aa", "ciphertext": "cc", "tag": "dd"}]"#);
+        let error = read_records(&input.path(), None, fields).err().unwrap();
+        assert!(error.contains("unknown field `tag`"), "{}", error);
+
+        let input = TempFile::new("encoding.json", r#"[{"key": "aa", "ciphertext": "cc", "encoding": "base32"}]"#);
+        assert!(read_records(&input.path(), None, fields).is_err());
+    }
+
+    #[test]
+    fn invalid_fields() {
+        assert_eq!(decode_field(" 00ff ", Encoding::Hex, "key", 0), Ok(vec![0, 255]));
+        assert_eq!(decode_field("AP8=", Encoding::Base64, "key", 0), Ok(vec![0, 255]));
+
+        let error = decode_field("0g", Encoding::Hex, "key", 3).err().unwrap();
+        assert!(error.starts_with("record 3: key is not valid"), "{}", error);
+        assert!(decode_field("abc", Encoding::Hex, "iv", 0).is_err());
+        assert!(decode_field("A*8=", Encoding::Base64, "ciphertext", 0).is_err());
+    }
+
+    #[test]
+    fn paths() {
+        assert!(read_input(&["a".to_string(), "b".to_string(), "c".to_string()], None, fields).is_err());
+        assert!(read_input(&["missing.json".to_string()], None, fields).err().unwrap().starts_with("unable to read missing.json"));
+    }
+}
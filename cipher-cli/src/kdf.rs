@@ -0,0 +1,215 @@
+use hkdf::Hkdf;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+// "Salted__" followed by 8 bytes of salt, written by `openssl enc`
+pub const OPENSSL_MAGIC: &[u8] = b"Salted__";
+pub const OPENSSL_HEADER_LENGTH: usize = 16;
+
+// default iteration count of `openssl enc -pbkdf2`
+const DEFAULT_ITERATIONS: u32 = 10000;
+
+/**
+ * Digest used by EVP_BytesToKey (`openssl enc -md`)
+ */
+pub enum MessageDigest {
+    Md5,
+    Sha256,
+}
+
+/**
+ * Function deriving key material from the passphrase and salt
+ */
+pub enum Kdf {
+    Pbkdf2 { iterations: u32 },
+    EvpBytesToKey { digest: MessageDigest },
+}
+
+/**
+ * Passphrase based key derivation options of the decrypt command
+ */
+pub struct KeyDerivation {
+    passphrase: Vec<u8>,
+    salt: Option<Vec<u8>>,
+    kdf: Kdf,
+    hkdf_info: Option<Vec<u8>>,
+}
+
+/**
+ * PBKDF2-HMAC-SHA256 (RFC 8018)
+ */
+pub fn pbkdf2_sha256(passphrase: &[u8], salt: &[u8], iterations: u32, n_bytes: usize) -> Vec<u8> {
+    let mut output = vec![0u8; n_bytes];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase, salt, iterations, &mut output);
+    output
+}
+
+/**
+ * HKDF-SHA256 (RFC 5869), derives independent keys for different purposes (info) from one master key
+ */
+pub fn hkdf_sha256(master_key: &[u8], salt: &[u8], info: &[u8], n_bytes: usize) -> Vec<u8> {
+    let mut output = vec![0u8; n_bytes];
+    Hkdf::<Sha256>::new(Some(salt), master_key)
+        .expand(info, &mut output)
+        .expect("HKDF output too long");
+    output
+}
+
+/**
+ * OpenSSL EVP_BytesToKey with a single iteration:
+ * D_i = digest(D_(i-1) || passphrase || salt), key material is D_1 || D_2 || ...
+ */
+pub fn evp_bytes_to_key(passphrase: &[u8], salt: &[u8], digest: &MessageDigest, n_bytes: usize) -> Vec<u8> {
+    let mut output: Vec<u8> = Vec::new();
+    let mut previous: Vec<u8> = Vec::new();
+
+    while output.len() < n_bytes {
+        let mut input = previous.clone();
+        input.extend(passphrase);
+        input.extend(salt);
+
+        previous = match digest {
+            MessageDigest::Md5 => Md5::digest(&input).to_vec(),
+            MessageDigest::Sha256 => Sha256::digest(&input).to_vec(),
+        };
+        output.extend(&previous);
+    }
+    output.truncate(n_bytes);
+    output
+}
+
+impl KeyDerivation {
+
+    /**
+     * Extracts --passphrase, --salt <hex>, --kdf <pbkdf2|evp>, --iterations, --md <sha256|md5> and --hkdf-info
+     * Returns the key derivation (None without passphrase) and the remaining arguments
+     */
+    pub fn from_args(args: &[String]) -> Result<(Option<KeyDerivation>, Vec<String>), String> {
+        let mut passphrase: Option<String> = None;
+        let mut salt: Option<Vec<u8>> = None;
+        let mut kdf_name = "pbkdf2".to_string();
+        let mut iterations = DEFAULT_ITERATIONS;
+        let mut digest = MessageDigest::Sha256;
+        let mut hkdf_info: Option<Vec<u8>> = None;
+        let mut remaining: Vec<String> = Vec::new();
+
+        let mut args_iter = args.iter();
+        while let Some(arg) = args_iter.next() {
+            let mut value = || args_iter.next().ok_or(format!("{} requires a value", arg));
+            match arg.as_str() {
+                "--passphrase" => passphrase = Some(value()?.clone()),
+                "--salt" => salt = Some(hex::decode(value()?).map_err(|x| format!("invalid salt: {}", x))?),
+                "--kdf" => kdf_name = value()?.clone(),
+                "--iterations" => iterations = value()?.parse().map_err(|x| format!("invalid iterations: {}", x))?,
+                "--md" => digest = match value()?.as_str() {
+                    "md5" => MessageDigest::Md5,
+                    "sha256" => MessageDigest::Sha256,
+                    other => return Err(format!("unknown digest {}, expected md5 or sha256", other)),
+                },
+                "--hkdf-info" => hkdf_info = Some(value()?.as_bytes().to_vec()),
+                _ => remaining.push(arg.clone()),
+            }
+        }
+
+        let kdf = match kdf_name.as_str() {
+            "pbkdf2" if iterations == 0 => return Err("iterations must be positive".to_string()),
+            "pbkdf2" => Kdf::Pbkdf2 { iterations },
+            "evp" => Kdf::EvpBytesToKey { digest },
+            other => return Err(format!("unknown kdf {}, expected pbkdf2 or evp", other)),
+        };
+
+        let key_derivation = passphrase.map(|passphrase| KeyDerivation {
+            passphrase: passphrase.into_bytes(),
+            salt,
+            kdf,
+            hkdf_info,
+        });
+        Ok((key_derivation, remaining))
+    }
+
+    /**
+     * Derives n bytes of key material from the passphrase and the salt
+     */
+    fn derive(&self, salt: &[u8], n_bytes: usize) -> Vec<u8> {
+        match &self.kdf {
+            Kdf::Pbkdf2 { iterations } => pbkdf2_sha256(&self.passphrase, salt, *iterations, n_bytes),
+            Kdf::EvpBytesToKey { digest } => evp_bytes_to_key(&self.passphrase, salt, digest, n_bytes),
+        }
+    }
+
+    /**
+     * Salt given by the --salt argument
+     */
+    pub fn salt(&self) -> Option<&[u8]> {
+        self.salt.as_deref()
+    }
+
+    /**
+     * Derives key and IV the way `openssl enc` does from the salt of its header:
+     * key_length bytes of key followed by the 16 byte IV (the nonce in CTR mode), from a single derivation
+     */
+    pub fn derive_key_iv(&self, salt: &[u8], key_length: usize) -> (Vec<u8>, Vec<u8>) {
+        let key_iv = self.derive(salt, key_length + 16);
+        (key_iv[..key_length].to_vec(), key_iv[key_length..].to_vec())
+    }
+
+    /**
+     * Derives the AES-128 key of a ciphertext, returns the key and the IV-prefixed ciphertext
+     * OpenSSL `enc` ciphertexts (Salted__ header) carry the salt and derive both key and IV,
+     * other ciphertexts use the --salt argument and keep their IV prefix
+     */
+    pub fn derive_message_key(&self, ciphertext: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+        let (key, iv, body) = if ciphertext.starts_with(OPENSSL_MAGIC) {
+            if ciphertext.len() < OPENSSL_HEADER_LENGTH {
+                return Err("OpenSSL header is truncated".to_string());
+            }
+            let (key, iv) = self.derive_key_iv(&ciphertext[OPENSSL_MAGIC.len()..OPENSSL_HEADER_LENGTH], 16);
+            (key, iv, &ciphertext[OPENSSL_HEADER_LENGTH..])
+        } else {
+            let salt = self.salt.as_ref().ok_or("--salt is required for ciphertexts without OpenSSL header")?;
+            (self.derive(salt, 16), Vec::new(), ciphertext)
+        };
+
+        // separate the cipher key from the passphrase derived master key
+        let key = match &self.hkdf_info {
+            Some(info) => hkdf_sha256(&key, &[], info, 16),
+            None => key,
+        };
+
+        let mut iv_ciphertext = iv;
+        iv_ciphertext.extend(body);
+        Ok((key, iv_ciphertext))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pbkdf2_rfc7914() {
+        assert_eq!(hex::encode(pbkdf2_sha256(b"passwd", b"salt", 1, 64)),
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+             49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783");
+    }
+
+    #[test]
+    fn hkdf_rfc5869() {
+        let salt: Vec<u8> = (0x00..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+        assert_eq!(hex::encode(hkdf_sha256(&[0x0b; 22], &salt, &info, 42)),
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865");
+    }
+
+    #[test]
+    fn evp_bytes_to_key_openssl() {
+        // openssl enc -aes-128-cbc -md md5 -pass pass:fixture -S 0102030405060708 -P
+        let args: Vec<String> = ["--passphrase", "fixture", "--kdf", "evp", "--md", "md5"].iter().map(|x| x.to_string()).collect();
+        let (key_derivation, remaining) = KeyDerivation::from_args(&args).unwrap();
+        assert!(remaining.is_empty());
+
+        let (key, iv) = key_derivation.unwrap().derive_key_iv(&[1, 2, 3, 4, 5, 6, 7, 8], 16);
+        assert_eq!(hex::encode(key), "c16f71b728839c5fb21dbc8f43dae674");
+        assert_eq!(hex::encode(iv), "35f64d7b46733a40bb614c353db2b042");
+    }
+}
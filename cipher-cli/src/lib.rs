@@ -0,0 +1,13 @@
+/*!
+ * Command line parts shared by the decryption projects
+ *
+ * - `input` - structured (JSON) and legacy (hex lines) input files of keys and ciphertexts
+ * - `kdf` - passphrase based key derivation (PBKDF2, EVP_BytesToKey, HKDF)
+ * - `openssl` - files and options of `openssl enc`
+ * - `output` - output modes of the decrypted plaintexts
+ */
+
+pub mod input;
+pub mod kdf;
+pub mod openssl;
+pub mod output;
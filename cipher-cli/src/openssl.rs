@@ -0,0 +1,237 @@
+use crate::kdf::{KeyDerivation, OPENSSL_HEADER_LENGTH, OPENSSL_MAGIC};
+
+// `openssl enc -a` wraps base64 lines at 64 characters
+const BASE64_LINE_LENGTH: usize = 64;
+
+// base64 of "Salted__", recognized even without the -a flag
+const BASE64_MAGIC: &str = "U2FsdGVkX1";
+
+/**
+ * Contents of an `openssl enc` file: optional salt header and the raw ciphertext (without IV)
+ */
+pub struct Container {
+    pub salt: Option<Vec<u8>>,
+    pub ciphertext: Vec<u8>,
+}
+
+/**
+ * `openssl enc` ciphers, a command supports some of them and selects one with --cipher
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum EncCipher {
+    Aes128Cbc,
+    Aes128Ctr,
+    Aes256Ctr,
+}
+
+impl EncCipher {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EncCipher::Aes128Cbc => "aes-128-cbc",
+            EncCipher::Aes128Ctr => "aes-128-ctr",
+            EncCipher::Aes256Ctr => "aes-256-ctr",
+        }
+    }
+
+    /**
+     * Parses the name of one of the supported ciphers
+     */
+    pub fn from_name(name: &str, supported: &[EncCipher]) -> Result<EncCipher, String> {
+        supported.iter().cloned().find(|x| x.name() == name).ok_or_else(|| {
+            let names: Vec<&str> = supported.iter().map(|x| x.name()).collect();
+            format!("unsupported cipher {}, expected {}", name, names.join(" or "))
+        })
+    }
+
+    pub fn key_length(&self) -> usize {
+        match self {
+            EncCipher::Aes128Cbc | EncCipher::Aes128Ctr => 16,
+            EncCipher::Aes256Ctr => 32,
+        }
+    }
+}
+
+/**
+ * Source of key and IV: explicit hex values (-K/-iv) or a passphrase
+ */
+pub enum KeySource {
+    Explicit { key: Vec<u8>, iv: Vec<u8> },
+    Passphrase(KeyDerivation),
+}
+
+/**
+ * Options of the openssl-encrypt and openssl-decrypt commands
+ */
+pub struct OpensslOptions {
+    pub base64: bool,
+    pub cipher: EncCipher,
+    pub key_source: KeySource,
+}
+
+/**
+ * Decodes a hex argument of the given length in bytes
+ */
+fn decode_hex_arg(value: &str, name: &str, length: usize) -> Result<Vec<u8>, String> {
+    let decoded = hex::decode(value).map_err(|x| format!("invalid {}: {}", name, x))?;
+    match decoded.len() == length {
+        true => Ok(decoded),
+        false => Err(format!("{} must be {} bytes long, got {}", name, length, decoded.len())),
+    }
+}
+
+/**
+ * Extracts -a/--base64, --cipher <name>, -K <hex>, -iv <hex> and the passphrase options from the arguments
+ * The cipher is one of the supported ones, the first by default
+ * Returns the options and the remaining arguments
+ */
+pub fn parse_openssl_args(args: &[String], supported: &[EncCipher]) -> Result<(OpensslOptions, Vec<String>), String> {
+    let (key_derivation, args) = KeyDerivation::from_args(args)?;
+
+    let mut base64 = false;
+    let mut cipher = supported[0];
+    let mut key_hex: Option<String> = None;
+    let mut iv: Option<Vec<u8>> = None;
+    let mut remaining: Vec<String> = Vec::new();
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        let mut value = || args_iter.next().ok_or(format!("{} requires a value", arg));
+        match arg.as_str() {
+            "-a" | "--base64" => base64 = true,
+            "--cipher" => cipher = EncCipher::from_name(value()?, supported)?,
+            "-K" => key_hex = Some(value()?.clone()),
+            "-iv" => iv = Some(decode_hex_arg(value()?, "iv", 16)?),
+            _ => remaining.push(arg.clone()),
+        }
+    }
+
+    // the key length depends on the cipher, which may come after -K
+    let key = match key_hex {
+        Some(key_hex) => Some(decode_hex_arg(&key_hex, "key", cipher.key_length())?),
+        None => None,
+    };
+
+    let key_source = match (key, iv, key_derivation) {
+        (Some(key), Some(iv), None) => KeySource::Explicit { key, iv },
+        (None, None, Some(key_derivation)) => KeySource::Passphrase(key_derivation),
+        (Some(_), None, None) => return Err("-K requires -iv".to_string()),
+        _ => return Err("expected either -K and -iv or --passphrase".to_string()),
+    };
+    Ok((OpensslOptions { base64, cipher, key_source }, remaining))
+}
+
+/**
+ * Parses the contents of an `openssl enc` file (binary or base64 armoured)
+ */
+pub fn parse_container(data: &[u8], base64: bool) -> Result<Container, String> {
+    let binary = if base64 || data.starts_with(BASE64_MAGIC.as_bytes()) {
+        let armoured: Vec<u8> = data.iter().cloned().filter(|x| !x.is_ascii_whitespace()).collect();
+        base64::decode(armoured).map_err(|x| format!("invalid base64: {}", x))?
+    } else {
+        data.to_vec()
+    };
+
+    if !binary.starts_with(OPENSSL_MAGIC) {
+        return Ok(Container { salt: None, ciphertext: binary });
+    }
+    if binary.len() < OPENSSL_HEADER_LENGTH {
+        return Err("OpenSSL header is truncated".to_string());
+    }
+
+    Ok(Container {
+        salt: Some(binary[OPENSSL_MAGIC.len()..OPENSSL_HEADER_LENGTH].to_vec()),
+        ciphertext: binary[OPENSSL_HEADER_LENGTH..].to_vec(),
+    })
+}
+
+/**
+ * Serializes the container the way `openssl enc` writes it
+ */
+pub fn serialize_container(container: &Container, base64: bool) -> Vec<u8> {
+    let mut binary: Vec<u8> = Vec::new();
+    if let Some(salt) = &container.salt {
+        binary.extend(OPENSSL_MAGIC);
+        binary.extend(salt);
+    }
+    binary.extend(&container.ciphertext);
+
+    if !base64 {
+        return binary;
+    }
+
+    let mut armoured: Vec<u8> = Vec::new();
+    for line in base64::encode(binary).as_bytes().chunks(BASE64_LINE_LENGTH) {
+        armoured.extend(line);
+        armoured.push(b'\n');
+    }
+    armoured
+}
+
+impl OpensslOptions {
+
+    /**
+     * Returns key and IV for a container with the given salt
+     * A salted container was encrypted with a passphrase, explicit keys would only decrypt it to garbage
+     */
+    pub fn key_iv(&self, salt: Option<&[u8]>) -> Result<(Vec<u8>, Vec<u8>), String> {
+        match (&self.key_source, salt) {
+            (KeySource::Explicit { key, iv }, None) => Ok((key.clone(), iv.clone())),
+            (KeySource::Explicit { .. }, Some(_)) => Err("file has a Salted__ header, it is decrypted with a passphrase and not -K/-iv".to_string()),
+            (KeySource::Passphrase(key_derivation), Some(salt)) => Ok(key_derivation.derive_key_iv(salt, self.cipher.key_length())),
+            (KeySource::Passphrase(_), None) => Err("passphrase requires a Salted__ header".to_string()),
+        }
+    }
+
+    /**
+     * Salt for a new container: the --salt argument or 8 random bytes, none for explicit keys
+     */
+    pub fn new_salt(&self) -> Result<Option<Vec<u8>>, String> {
+        match &self.key_source {
+            KeySource::Explicit { .. } => Ok(None),
+            KeySource::Passphrase(key_derivation) => match key_derivation.salt() {
+                Some(salt) if salt.len() == OPENSSL_HEADER_LENGTH - OPENSSL_MAGIC.len() => Ok(Some(salt.to_vec())),
+                Some(_) => Err("salt of OpenSSL files must be 8 bytes long".to_string()),
+                None => {
+                    let mut salt = vec![0u8; OPENSSL_HEADER_LENGTH - OPENSSL_MAGIC.len()];
+                    getrandom::getrandom(&mut salt).map_err(|x| x.to_string())?;
+                    Ok(Some(salt))
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(x: &[&str]) -> Vec<String> {
+        x.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn supported_ciphers() {
+        let supported = &[EncCipher::Aes128Ctr, EncCipher::Aes256Ctr];
+        let (options, remaining) = parse_openssl_args(&args(&["--passphrase", "p", "in.bin"]), supported).unwrap();
+        assert!(options.cipher == EncCipher::Aes128Ctr);
+        assert_eq!(remaining, ["in.bin"]);
+
+        let (options, _) = parse_openssl_args(&args(&["--cipher", "aes-256-ctr", "--passphrase", "p"]), supported).unwrap();
+        assert!(options.cipher == EncCipher::Aes256Ctr);
+        assert!(parse_openssl_args(&args(&["--cipher", "aes-128-cbc", "--passphrase", "p"]), supported).is_err());
+    }
+
+    #[test]
+    fn container_round_trip() {
+        let container = Container { salt: Some(vec![7u8; 8]), ciphertext: (0..100).collect() };
+        for base64 in [false, true] {
+            let data = serialize_container(&container, base64);
+            // base64 armouring is recognized without the flag
+            let parsed = parse_container(&data, false).unwrap();
+            assert_eq!(parsed.salt, container.salt);
+            assert_eq!(parsed.ciphertext, container.ciphertext);
+        }
+        assert!(parse_container(b"Salted__1234", false).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use std::fs;
+use std::path::PathBuf;
+
+/**
+ * How decrypted plaintexts are presented
+ */
+pub enum OutputMode {
+    // raw bytes written to message_<index>.bin files in the directory
+    Raw(PathBuf),
+    Hex,
+    Base64,
+    // UTF-8 text, invalid sequences are replaced and reported
+    Utf8,
+    // printable ascii as is, everything else escaped
+    Escaped,
+}
+
+/**
+ * Extracts --output <raw|hex|base64|utf8|escaped> and --out-dir <dir> from the arguments
+ * Returns the output mode (utf8 by default) and the remaining arguments
+ */
+pub fn parse_output_args(args: &[String]) -> Result<(OutputMode, Vec<String>), String> {
+    let mut mode_name = "utf8".to_string();
+    let mut out_dir: Option<PathBuf> = None;
+    let mut remaining: Vec<String> = Vec::new();
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--output" => mode_name = args_iter.next().ok_or("--output requires a value")?.clone(),
+            "--out-dir" => out_dir = Some(PathBuf::from(args_iter.next().ok_or("--out-dir requires a value")?)),
+            _ => remaining.push(arg.clone()),
+        }
+    }
+
+    let mode = match mode_name.as_str() {
+        "raw" => OutputMode::Raw(out_dir.ok_or("--output raw requires --out-dir")?),
+        "hex" => OutputMode::Hex,
+        "base64" => OutputMode::Base64,
+        "utf8" => OutputMode::Utf8,
+        "escaped" => OutputMode::Escaped,
+        other => return Err(format!("unknown output mode {}, expected raw, hex, base64, utf8 or escaped", other)),
+    };
+    Ok((mode, remaining))
+}
+
+/**
+ * Decodes UTF-8 replacing invalid sequences, returns the text and offsets of the replaced sequences
+ */
+fn decode_utf8(bytes: &[u8]) -> (String, Vec<usize>) {
+    let mut text = String::new();
+    let mut invalid_offsets: Vec<usize> = Vec::new();
+    let mut offset = 0;
+
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        offset += chunk.valid().len();
+
+        if !chunk.invalid().is_empty() {
+            text.push(char::REPLACEMENT_CHARACTER);
+            invalid_offsets.push(offset);
+            offset += chunk.invalid().len();
+        }
+    }
+    (text, invalid_offsets)
+}
+
+/**
+ * Writes the plaintext of the message with the given index in the selected output mode
+ */
+pub fn write_plaintext(plaintext: &[u8], index: usize, mode: &OutputMode) -> Result<(), String> {
+    match mode {
+        OutputMode::Raw(dir) => {
+            let path = dir.join(format!("message_{}.bin", index));
+            fs::write(&path, plaintext).map_err(|x| format!("unable to write {}: {}", path.display(), x))?;
+            println!("{} bytes written to {}", plaintext.len(), path.display());
+        }
+        OutputMode::Hex => println!("{}", hex::encode(plaintext)),
+        OutputMode::Base64 => println!("{}", base64::encode(plaintext)),
+        OutputMode::Utf8 => {
+            let (text, invalid_offsets) = decode_utf8(plaintext);
+            println!("{}", text);
+            if !invalid_offsets.is_empty() {
+                eprintln!("{} invalid UTF-8 sequence(s) replaced at byte offset(s) {:?}",
+                    invalid_offsets.len(), invalid_offsets);
+            }
+        }
+        OutputMode::Escaped => println!("{}", plaintext.escape_ascii()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn valid_utf8() {
+        assert_eq!(decode_utf8("plain ascii, h\u{e9}llo \u{1f600}".as_bytes()), ("plain ascii, h\u{e9}llo \u{1f600}".to_string(), vec![]));
+        assert_eq!(decode_utf8(b""), (String::new(), vec![]));
+    }
+
+    #[test]
+    fn truncated_sequences() {
+        // the first two bytes of the euro sign e2 82 ac, at the end and in the middle
+        assert_eq!(decode_utf8(b"ab\xe2\x82"), ("ab\u{fffd}".to_string(), vec![2]));
+        assert_eq!(decode_utf8(b"a\xe2\x82bc"), ("a\u{fffd}bc".to_string(), vec![1]));
+
+        // the offsets count bytes, not characters
+        assert_eq!(decode_utf8(b"\xc3\xa9\xf0\x9f\x98"), ("\u{e9}\u{fffd}".to_string(), vec![2]));
+    }
+
+    #[test]
+    fn stray_continuation_bytes() {
+        // every stray continuation byte is replaced on its own
+        assert_eq!(decode_utf8(b"x\x80y\x80\xbf"), ("x\u{fffd}y\u{fffd}\u{fffd}".to_string(), vec![1, 3, 4]));
+        assert_eq!(decode_utf8(b"\xe2\x82\xac\xac"), ("\u{20ac}\u{fffd}".to_string(), vec![3]));
+    }
+
+    #[test]
+    fn output_args() {
+        let (mode, remaining) = parse_output_args(&args(&["a.txt", "b.txt"])).unwrap();
+        assert!(matches!(mode, OutputMode::Utf8));
+        assert_eq!(remaining, ["a.txt", "b.txt"]);
+
+        let (mode, remaining) = parse_output_args(&args(&["--output", "hex", "a.txt"])).unwrap();
+        assert!(matches!(mode, OutputMode::Hex));
+        assert_eq!(remaining, ["a.txt"]);
+
+        assert!(matches!(parse_output_args(&args(&["--output", "base64"])), Ok((OutputMode::Base64, _))));
+        assert!(matches!(parse_output_args(&args(&["--output", "utf8"])), Ok((OutputMode::Utf8, _))));
+        assert!(matches!(parse_output_args(&args(&["--output", "escaped"])), Ok((OutputMode::Escaped, _))));
+
+        let (mode, remaining) = parse_output_args(&args(&["--out-dir", "plaintexts", "--output", "raw", "in.json"])).unwrap();
+        assert!(matches!(mode, OutputMode::Raw(dir) if dir.as_path() == Path::new("plaintexts")));
+        assert_eq!(remaining, ["in.json"]);
+    }
+
+    #[test]
+    fn unknown_flags() {
+        // flags of the calling command are left to it, in their order
+        let (mode, remaining) = parse_output_args(&args(&["--mode", "cbc-cs1", "--output", "hex", "--verbose", "a.txt"])).unwrap();
+        assert!(matches!(mode, OutputMode::Hex));
+        assert_eq!(remaining, ["--mode", "cbc-cs1", "--verbose", "a.txt"]);
+    }
+
+    #[test]
+    fn invalid_output_args() {
+        for list in [&["--output", "binary"][..], &["--output"], &["--out-dir"], &["--output", "raw"], &["--output", "HEX"]] {
+            assert!(parse_output_args(&args(list)).is_err(), "{:?}", list);
+        }
+    }
+}